@@ -0,0 +1,59 @@
+// Lit terrain which blends a grass, rock and snow texture. The red, green
+// and blue vertex colors hold the weight of each texture.
+
+#import bevy_pbr::mesh_view_bindings
+#import bevy_pbr::mesh_bindings
+
+#import bevy_pbr::pbr_types
+#import bevy_pbr::utils
+#import bevy_pbr::clustered_forward
+#import bevy_pbr::lighting
+#import bevy_pbr::shadows
+#import bevy_pbr::pbr_functions
+
+@group(1) @binding(0)
+var grass_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var grass_sampler: sampler;
+@group(1) @binding(2)
+var rock_texture: texture_2d<f32>;
+@group(1) @binding(3)
+var rock_sampler: sampler;
+@group(1) @binding(4)
+var snow_texture: texture_2d<f32>;
+@group(1) @binding(5)
+var snow_sampler: sampler;
+
+struct FragmentInput {
+    @builtin(front_facing) is_front: bool,
+    @builtin(position) frag_coord: vec4<f32>,
+    #import bevy_pbr::mesh_vertex_output
+};
+
+@fragment
+fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
+    var pbr_input: PbrInput = pbr_input_new();
+
+    let grass = textureSample(grass_texture, grass_sampler, in.uv);
+    let rock = textureSample(rock_texture, rock_sampler, in.uv);
+    let snow = textureSample(snow_texture, snow_sampler, in.uv);
+    pbr_input.material.base_color = grass * in.color.r + rock * in.color.g + snow * in.color.b;
+    pbr_input.material.perceptual_roughness = 0.9;
+    pbr_input.material.metallic = 0.0;
+
+    pbr_input.frag_coord = in.frag_coord;
+    pbr_input.world_position = in.world_position;
+    pbr_input.world_normal = prepare_world_normal(in.world_normal, false, in.is_front);
+    pbr_input.is_orthographic = view.projection[3].w == 1.0;
+    pbr_input.N = normalize(pbr_input.world_normal);
+    pbr_input.V = calculate_view(in.world_position, pbr_input.is_orthographic);
+
+    var output_color = pbr(pbr_input);
+#ifdef TONEMAP_IN_SHADER
+    output_color = tone_mapping(output_color);
+#endif
+#ifdef DEBAND_DITHER
+    output_color = dither(output_color, in.frag_coord.xy);
+#endif
+    return output_color;
+}
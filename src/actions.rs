@@ -0,0 +1,402 @@
+use bevy::input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel};
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::HashMap;
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+use crate::camera::CameraSettings;
+use crate::ron_asset::{apply_ron_resource, RonResourcePlugin};
+
+/// Everything the player can do, independent of the device doing it.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    MoveForward,
+    Strafe,
+    Jump,
+    Sprint,
+    Crouch,
+    LookYaw,
+    LookPitch,
+    Zoom,
+    SwitchCameraMode,
+    Pause,
+    QuickSave,
+    QuickLoad,
+    Interact,
+}
+
+impl Action {
+    /// Actions which are applied as a change per frame, like camera rotation.
+    ///
+    /// Held inputs like keys or sticks are multiplied by the frame time for
+    /// these, so `scale` is in units per second for them.
+    pub fn is_delta(self) -> bool {
+        matches!(self, Action::LookYaw | Action::LookPitch | Action::Zoom)
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum InputSource {
+    Key(KeyCode),
+    MouseButton(MouseButton),
+    MouseMotionX,
+    MouseMotionY,
+    MouseWheel,
+    GamepadButton(GamepadButtonType),
+    /// Analog value of a gamepad button, like the triggers.
+    GamepadButtonAxis(GamepadButtonType),
+    GamepadAxis(GamepadAxisType),
+}
+impl InputSource {
+    fn is_delta(self) -> bool {
+        matches!(
+            self,
+            InputSource::MouseMotionX | InputSource::MouseMotionY | InputSource::MouseWheel
+        )
+    }
+}
+
+/// Maps one input source to an action.
+///
+/// Buttons contribute `scale` while held, mouse motion and the wheel
+/// contribute their delta of the current frame multiplied by `scale` and the
+/// sensitivity from [`CameraSettings`].
+/// Analog gamepad values below `deadzone` are ignored and the remaining
+/// range is rescaled to start at zero, so the deadzone has to be below 1.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct Binding {
+    pub source: InputSource,
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    #[serde(default, deserialize_with = "deserialize_deadzone")]
+    pub deadzone: f32,
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+fn deserialize_deadzone<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+    let deadzone = f32::deserialize(deserializer)?;
+    if (0.0..1.0).contains(&deadzone) {
+        Ok(deadzone)
+    } else {
+        Err(de::Error::custom(format!(
+            "deadzone must be at least 0 and below 1, got {}",
+            deadzone
+        )))
+    }
+}
+
+/// Input bindings, loaded from `assets/default.bindings.ron`.
+#[derive(Resource, Deserialize, Serialize, TypeUuid, Clone, Default, Debug)]
+#[uuid = "4ed6c619-85a9-4153-9a52-8e0d6a309824"]
+pub struct InputBindings(pub HashMap<Action, Vec<Binding>>);
+impl InputBindings {
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.0.get(&action).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Replaces the binding at `index`, or appends it if the action has fewer bindings.
+    pub fn rebind(&mut self, action: Action, index: usize, binding: Binding) {
+        let bindings = self.0.entry(action).or_default();
+        match bindings.get_mut(index) {
+            Some(existing) => *existing = binding,
+            None => bindings.push(binding),
+        }
+    }
+}
+
+/// Current value of every action, updated each frame from [`InputBindings`].
+#[derive(Resource, Default)]
+pub struct ActionState {
+    values: HashMap<Action, f32>,
+    previous_values: HashMap<Action, f32>,
+}
+impl ActionState {
+    pub fn value(&self, action: Action) -> f32 {
+        self.values.get(&action).copied().unwrap_or(0.0)
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.value(action).abs() >= 0.5
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        let previous = self.previous_values.get(&action).copied().unwrap_or(0.0);
+        self.pressed(action) && previous.abs() < 0.5
+    }
+}
+
+/// When set, the next pressed key, mouse or gamepad button replaces the binding at
+/// the given index of the action.
+///
+/// Rebinds only change the [`InputBindings`] resource, so they last for the
+/// current session. They aren't stored with the user settings and a hot
+/// reload of the bindings file replaces them.
+///
+/// Nothing in the game sets this yet, the menus have no controls page, so
+/// rebinding is only available to code for now.
+#[derive(Resource, Default)]
+pub struct PendingRebind(pub Option<(Action, usize)>);
+
+pub struct ActionPlugin;
+impl Plugin for ActionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(RonResourcePlugin::<InputBindings>::new(
+            "default.bindings.ron",
+            &["bindings.ron"],
+        ))
+        .init_resource::<ActionState>()
+        .init_resource::<PendingRebind>()
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            rebind_pending_action
+                .after(InputSystem)
+                .before(update_action_state),
+        )
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            update_action_state
+                .after(InputSystem)
+                .after(apply_ron_resource::<InputBindings>),
+        );
+    }
+}
+
+fn rebind_pending_action(
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut pending_rebind: ResMut<PendingRebind>,
+    mut input_bindings: ResMut<InputBindings>,
+) {
+    let Some((action, index)) = pending_rebind.0 else {
+        return;
+    };
+    let source = keys
+        .get_just_pressed()
+        .next()
+        .map(|key| InputSource::Key(*key))
+        .or_else(|| {
+            mouse_buttons
+                .get_just_pressed()
+                .next()
+                .map(|button| InputSource::MouseButton(*button))
+        })
+        .or_else(|| {
+            gamepad_buttons
+                .get_just_pressed()
+                .next()
+                .map(|button| InputSource::GamepadButton(button.button_type))
+        });
+    if let Some(source) = source {
+        // Keep the direction of axis bindings like `S` on `MoveForward`.
+        let scale = input_bindings
+            .bindings(action)
+            .get(index)
+            .map(|binding| binding.scale)
+            .unwrap_or(1.0);
+        input_bindings.rebind(
+            action,
+            index,
+            Binding {
+                source,
+                scale,
+                deadzone: 0.0,
+            },
+        );
+        pending_rebind.0 = None;
+    }
+}
+
+pub fn update_action_state(
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_button_axes: Res<Axis<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    mut mouse_motion_events: EventReader<MouseMotion>,
+    mut scroll_events: EventReader<MouseWheel>,
+    input_bindings: Res<InputBindings>,
+    camera_settings: Res<CameraSettings>,
+    mut action_state: ResMut<ActionState>,
+) {
+    let mouse_motion = mouse_motion_events
+        .iter()
+        .fold(Vec2::ZERO, |sum, event| sum + event.delta)
+        * camera_settings.mouse_sensitivity;
+    let scroll: f32 = scroll_events
+        .iter()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y * camera_settings.scroll_line_sensitivity,
+            MouseScrollUnit::Pixel => event.y * camera_settings.scroll_pixel_sensitivity,
+        })
+        .sum();
+
+    let action_state = &mut *action_state;
+    std::mem::swap(&mut action_state.values, &mut action_state.previous_values);
+    action_state.values.clear();
+    for (action, bindings) in input_bindings.0.iter() {
+        let value = bindings
+            .iter()
+            .map(|binding| {
+                let raw = match binding.source {
+                    InputSource::Key(key) => button_value(keys.pressed(key)),
+                    InputSource::MouseButton(button) => button_value(mouse_buttons.pressed(button)),
+                    InputSource::MouseMotionX => mouse_motion.x,
+                    InputSource::MouseMotionY => mouse_motion.y,
+                    InputSource::MouseWheel => scroll,
+                    InputSource::GamepadButton(button_type) => gamepads
+                        .iter()
+                        .map(|gamepad| {
+                            let button = GamepadButton::new(gamepad, button_type);
+                            button_value(gamepad_buttons.pressed(button))
+                        })
+                        .sum(),
+                    InputSource::GamepadButtonAxis(button_type) => gamepads
+                        .iter()
+                        .filter_map(|gamepad| {
+                            gamepad_button_axes.get(GamepadButton::new(gamepad, button_type))
+                        })
+                        .map(|value| apply_deadzone(value, binding.deadzone))
+                        .sum(),
+                    InputSource::GamepadAxis(axis_type) => gamepads
+                        .iter()
+                        .filter_map(|gamepad| {
+                            gamepad_axes.get(GamepadAxis::new(gamepad, axis_type))
+                        })
+                        .map(|value| apply_deadzone(value, binding.deadzone))
+                        .sum(),
+                };
+                if action.is_delta() && !binding.source.is_delta() {
+                    raw * binding.scale * time.delta_seconds()
+                } else {
+                    raw * binding.scale
+                }
+            })
+            .sum();
+        action_state.values.insert(*action, value);
+    }
+}
+
+fn button_value(pressed: bool) -> f32 {
+    if pressed {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+fn apply_deadzone(value: f32, deadzone: f32) -> f32 {
+    if value.abs() <= deadzone {
+        0.0
+    } else {
+        value.signum() * (value.abs() - deadzone) / (1.0 - deadzone)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::gamepad::{GamepadEventRaw, GamepadEventType, GamepadInfo};
+    use bevy::input::InputPlugin;
+
+    use super::*;
+
+    const GAMEPAD: Gamepad = Gamepad { id: 0 };
+
+    fn app(bindings: &str) -> App {
+        let mut app = App::new();
+        app.add_plugin(InputPlugin)
+            .init_resource::<Time>()
+            .init_resource::<CameraSettings>()
+            .init_resource::<ActionState>()
+            .insert_resource(ron::from_str::<InputBindings>(bindings).unwrap())
+            .add_system_to_stage(CoreStage::PreUpdate, update_action_state.after(InputSystem));
+        send(
+            &mut app,
+            GamepadEventType::Connected(GamepadInfo {
+                name: "Test gamepad".to_string(),
+            }),
+        );
+        app
+    }
+
+    fn send(app: &mut App, event_type: GamepadEventType) {
+        app.world
+            .send_event(GamepadEventRaw::new(GAMEPAD, event_type));
+        app.update();
+    }
+
+    #[test]
+    fn gamepad_axis_is_rescaled_after_deadzone() {
+        let mut app = app("({Strafe: [(source: GamepadAxis(LeftStickX), deadzone: 0.2)]})");
+
+        send(
+            &mut app,
+            GamepadEventType::AxisChanged(GamepadAxisType::LeftStickX, 0.6),
+        );
+        let value = app.world.resource::<ActionState>().value(Action::Strafe);
+        assert!((value - 0.5).abs() < 1e-5, "got {}", value);
+
+        send(
+            &mut app,
+            GamepadEventType::AxisChanged(GamepadAxisType::LeftStickX, -0.15),
+        );
+        assert_eq!(
+            app.world.resource::<ActionState>().value(Action::Strafe),
+            0.0
+        );
+    }
+
+    #[test]
+    fn gamepad_button_presses_action() {
+        let mut app = app("({Jump: [(source: GamepadButton(South))]})");
+        assert!(!app.world.resource::<ActionState>().pressed(Action::Jump));
+
+        send(
+            &mut app,
+            GamepadEventType::ButtonChanged(GamepadButtonType::South, 1.0),
+        );
+        let actions = app.world.resource::<ActionState>();
+        assert!(actions.pressed(Action::Jump));
+        assert!(actions.just_pressed(Action::Jump));
+
+        app.update();
+        let actions = app.world.resource::<ActionState>();
+        assert!(actions.pressed(Action::Jump));
+        assert!(!actions.just_pressed(Action::Jump));
+
+        send(
+            &mut app,
+            GamepadEventType::ButtonChanged(GamepadButtonType::South, 0.0),
+        );
+        assert!(!app.world.resource::<ActionState>().pressed(Action::Jump));
+    }
+
+    #[test]
+    fn deadzone_rescales_remaining_range() {
+        assert_eq!(apply_deadzone(0.1, 0.2), 0.0);
+        assert_eq!(apply_deadzone(-0.2, 0.2), 0.0);
+        assert_eq!(apply_deadzone(1.0, 0.2), 1.0);
+        assert_eq!(apply_deadzone(-1.0, 0.2), -1.0);
+        assert!((apply_deadzone(-0.6, 0.2) + 0.5).abs() < 1e-6);
+        assert_eq!(apply_deadzone(0.3, 0.0), 0.3);
+    }
+
+    #[test]
+    fn deadzone_of_one_or_more_is_rejected() {
+        assert!(
+            ron::from_str::<Binding>("(source: GamepadAxis(LeftStickX), deadzone: 0.5)").is_ok()
+        );
+        assert!(
+            ron::from_str::<Binding>("(source: GamepadAxis(LeftStickX), deadzone: 1.0)").is_err()
+        );
+        assert!(
+            ron::from_str::<Binding>("(source: GamepadAxis(LeftStickX), deadzone: -0.1)").is_err()
+        );
+    }
+}
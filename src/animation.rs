@@ -0,0 +1,731 @@
+use bevy::animation::{Keyframes, VariableCurve};
+use bevy::prelude::*;
+use bevy::scene::SceneInstance;
+use bevy::transform::TransformSystem;
+use bevy_rapier3d::prelude::*;
+use serde::Deserialize;
+
+use crate::game_assets::GameAssets;
+use crate::movement::{keyboard_input, Grounded, MovementSettings};
+use crate::AppState;
+
+/// Time in seconds in which the pose blends from one clip to the next.
+const CROSSFADE_DURATION: f32 = 0.25;
+/// Horizontal speed below which the player counts as standing still.
+const IDLE_SPEED: f32 = 0.1;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Locomotion {
+    Idle,
+    Walk,
+    Run,
+    /// In the air, both while jumping and falling.
+    Jump,
+}
+impl Locomotion {
+    fn from_movement(
+        velocity: &Velocity,
+        grounded: &Grounded,
+        movement_settings: &MovementSettings,
+    ) -> Self {
+        // Walking off a ledge keeps the walk cycle during coyote time, so
+        // small bumps don't flash the jump clip.
+        let airborne =
+            !grounded.grounded && (grounded.coyote_timer <= 0.0 || velocity.linvel.y > 0.0);
+        if airborne {
+            return Locomotion::Jump;
+        }
+        let speed = Vec2::new(velocity.linvel.x, velocity.linvel.z).length();
+        if speed < IDLE_SPEED {
+            Locomotion::Idle
+        } else if speed > (movement_settings.walk_speed + movement_settings.sprint_speed) / 2.0 {
+            Locomotion::Run
+        } else {
+            Locomotion::Walk
+        }
+    }
+
+    /// The jump clip plays once and holds its last pose, the others loop.
+    fn repeats(self) -> bool {
+        self != Locomotion::Jump
+    }
+}
+
+/// Fires an [`AnimationEvent`] when the clip passes `time`, in seconds from
+/// the start of the clip.
+#[derive(Deserialize, Clone, Debug)]
+pub struct AnimationMarker {
+    pub time: f32,
+    pub name: String,
+}
+
+/// A clip in the asset manifest.
+#[derive(Deserialize, Debug)]
+pub struct ClipSettings {
+    /// Asset path like `human.glb#Animation0`.
+    pub path: String,
+    #[serde(default)]
+    pub events: Vec<AnimationMarker>,
+    /// Moves the player by the horizontal translation of the root bone
+    /// instead of its velocity, see [`RootBone`].
+    #[serde(default)]
+    pub root_motion: bool,
+}
+
+/// A loaded clip of [`GameAssets`].
+pub struct PlayerClip {
+    pub handle: Handle<AnimationClip>,
+    pub events: Vec<AnimationMarker>,
+    pub root_motion: bool,
+}
+impl PlayerClip {
+    pub fn load(settings: &ClipSettings, asset_server: &AssetServer) -> Self {
+        PlayerClip {
+            handle: asset_server.load(settings.path.as_str()),
+            events: settings.events.clone(),
+            root_motion: settings.root_motion,
+        }
+    }
+}
+
+/// Name of the bone which carries the root motion, like `Hips`.
+///
+/// Its translation is taken as relative to the player, so the bone and its
+/// parents must not be rotated or scaled.
+#[derive(Resource, Deserialize, Clone, Debug)]
+pub struct RootBone(pub String);
+
+/// Sent when a playing clip passes one of its markers.
+pub struct AnimationEvent {
+    /// Entity with the [`LocomotionAnimator`].
+    pub entity: Entity,
+    pub name: String,
+}
+
+/// One clip for each [`Locomotion`] state. States without a clip keep
+/// playing the previous one.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct LocomotionClips<T> {
+    pub idle: Option<T>,
+    pub walk: Option<T>,
+    pub run: Option<T>,
+    pub jump: Option<T>,
+}
+impl<T> LocomotionClips<T> {
+    pub fn get(&self, state: Locomotion) -> Option<&T> {
+        match state {
+            Locomotion::Idle => self.idle.as_ref(),
+            Locomotion::Walk => self.walk.as_ref(),
+            Locomotion::Run => self.run.as_ref(),
+            Locomotion::Jump => self.jump.as_ref(),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        [&self.idle, &self.walk, &self.run, &self.jump]
+            .into_iter()
+            .flatten()
+    }
+
+    pub fn map<U>(&self, mut f: impl FnMut(&T) -> U) -> LocomotionClips<U> {
+        LocomotionClips {
+            idle: self.idle.as_ref().map(&mut f),
+            walk: self.walk.as_ref().map(&mut f),
+            run: self.run.as_ref().map(&mut f),
+            jump: self.jump.as_ref().map(&mut f),
+        }
+    }
+}
+
+/// Plays the locomotion clips of [`GameAssets`] on the `AnimationPlayer` in
+/// the scene of this entity, picked from its [`Velocity`] and [`Grounded`].
+///
+/// Scenes without an `AnimationPlayer`, like ones whose clips are in a
+/// separate file, get one on the node the clips are rooted at. Removed again
+/// if the scene has neither.
+#[derive(Component, Default)]
+pub struct LocomotionAnimator {
+    /// Found once the scene has spawned.
+    animation_player: Option<Entity>,
+    /// Entity of the [`RootBone`], if the scene has one.
+    root_bone: Option<Entity>,
+    state: Option<Locomotion>,
+    /// Elapsed time of the clip when events and root motion were last
+    /// processed.
+    last_elapsed: f32,
+}
+
+/// Blends from the pose at the time of a clip change to the new clip, as
+/// the `AnimationPlayer` only plays a single clip at a time.
+#[derive(Component)]
+struct Crossfade {
+    from: Vec<(Entity, Transform)>,
+    elapsed: f32,
+}
+
+pub struct PlayerAnimationPlugin;
+impl Plugin for PlayerAnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AnimationEvent>()
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(find_animation_players)
+                    .with_system(
+                        update_locomotion
+                            .after(find_animation_players)
+                            .after(keyboard_input),
+                    )
+                    .with_system(advance_clips.after(update_locomotion))
+                    .with_system(log_animation_events.after(advance_clips)),
+            )
+            .add_system_set(SystemSet::on_enter(AppState::Paused).with_system(pause_animations))
+            .add_system_set(SystemSet::on_exit(AppState::Paused).with_system(resume_animations))
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                pin_root_bones
+                    .after(bevy::animation::animation_player)
+                    .before(apply_crossfade),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                apply_crossfade
+                    .after(bevy::animation::animation_player)
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+fn find_animation_players(
+    mut commands: Commands,
+    scene_spawner: Res<SceneSpawner>,
+    game_assets: Res<GameAssets>,
+    clips: Res<Assets<AnimationClip>>,
+    root_bone: Option<Res<RootBone>>,
+    mut animator_query: Query<(Entity, &mut LocomotionAnimator, &SceneInstance)>,
+    animation_player_query: Query<(), With<AnimationPlayer>>,
+    name_query: Query<&Name>,
+) {
+    // Node the clips animate from, the first part of their paths.
+    let clip_root = game_assets
+        .player_animations
+        .iter()
+        .filter_map(|clip| clips.get(&clip.handle))
+        .find_map(|clip| clip.curves().keys().next())
+        .and_then(|path| path.parts.first());
+
+    for (entity, mut animator, scene_instance) in animator_query.iter_mut() {
+        if animator.animation_player.is_some() || !scene_spawner.instance_is_ready(**scene_instance)
+        {
+            continue;
+        }
+        animator.animation_player = scene_spawner
+            .iter_instance_entities(**scene_instance)
+            .find(|scene_entity| animation_player_query.contains(*scene_entity))
+            .or_else(|| {
+                let clip_root = clip_root?;
+                let scene_entity = scene_spawner
+                    .iter_instance_entities(**scene_instance)
+                    .find(|scene_entity| {
+                        name_query
+                            .get(*scene_entity)
+                            .map(|name| name == clip_root)
+                            .unwrap_or(false)
+                    })?;
+                commands
+                    .entity(scene_entity)
+                    .insert(AnimationPlayer::default());
+                Some(scene_entity)
+            });
+        if animator.animation_player.is_none() {
+            debug!("The scene of {:?} has no animations", entity);
+            commands.entity(entity).remove::<LocomotionAnimator>();
+            continue;
+        }
+        if let Some(root_bone) = &root_bone {
+            animator.root_bone =
+                scene_spawner
+                    .iter_instance_entities(**scene_instance)
+                    .find(|scene_entity| {
+                        name_query
+                            .get(*scene_entity)
+                            .map(|name| name.as_str() == root_bone.0)
+                            .unwrap_or(false)
+                    });
+            if animator.root_bone.is_none() {
+                warn!("The scene of {:?} has no root bone {}", entity, root_bone.0);
+            }
+        }
+    }
+}
+
+fn update_locomotion(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    movement_settings: Res<MovementSettings>,
+    mut animator_query: Query<(&mut LocomotionAnimator, &Velocity, &Grounded)>,
+    mut animation_player_query: Query<&mut AnimationPlayer>,
+    children_query: Query<&Children>,
+    transform_query: Query<&Transform>,
+) {
+    for (mut animator, velocity, grounded) in animator_query.iter_mut() {
+        let Some(animation_player_entity) = animator.animation_player else {
+            continue;
+        };
+        let state = Locomotion::from_movement(velocity, grounded, &movement_settings);
+        if animator.state == Some(state) {
+            continue;
+        }
+        let Some(clip) = game_assets.player_animations.get(state) else {
+            continue;
+        };
+        let Ok(mut animation_player) = animation_player_query.get_mut(animation_player_entity)
+        else {
+            continue;
+        };
+
+        // The first clip starts right away, later ones fade in.
+        if animator.state.is_some() {
+            commands.entity(animation_player_entity).insert(Crossfade {
+                from: pose(animation_player_entity, &children_query, &transform_query),
+                elapsed: 0.0,
+            });
+        }
+        // Unlike `play`, this also restarts a clip shared with the previous
+        // state, which resets the elapsed time along with `last_elapsed`.
+        animation_player.start(clip.handle.clone());
+        if state.repeats() {
+            animation_player.repeat();
+        }
+        animator.state = Some(state);
+        animator.last_elapsed = 0.0;
+    }
+}
+
+/// Sends the events of the clips and moves the players by their root motion,
+/// for the time the clips advanced since the last frame.
+fn advance_clips(
+    game_assets: Res<GameAssets>,
+    clips: Res<Assets<AnimationClip>>,
+    root_bone: Option<Res<RootBone>>,
+    mut events: EventWriter<AnimationEvent>,
+    mut animator_query: Query<(
+        Entity,
+        &mut LocomotionAnimator,
+        &Transform,
+        &mut KinematicCharacterController,
+    )>,
+    animation_player_query: Query<&AnimationPlayer>,
+) {
+    for (entity, mut animator, transform, mut character_controller) in animator_query.iter_mut() {
+        let (Some(animation_player_entity), Some(state)) =
+            (animator.animation_player, animator.state)
+        else {
+            continue;
+        };
+        let Some(clip) = game_assets.player_animations.get(state) else {
+            continue;
+        };
+        let (Ok(animation_player), Some(animation_clip)) = (
+            animation_player_query.get(animation_player_entity),
+            clips.get(&clip.handle),
+        ) else {
+            continue;
+        };
+        let previous = animator.last_elapsed;
+        let elapsed = animation_player.elapsed();
+        animator.last_elapsed = elapsed;
+        let duration = animation_clip.duration();
+        if elapsed <= previous || duration <= 0.0 {
+            continue;
+        }
+
+        // Looping clips fire their markers once per cycle.
+        let cycles = if state.repeats() {
+            (previous / duration) as u32..=(elapsed / duration) as u32
+        } else {
+            0..=0
+        };
+        for cycle in cycles {
+            for marker in clip.events.iter() {
+                let time = cycle as f32 * duration + marker.time;
+                if previous <= time && time < elapsed {
+                    events.send(AnimationEvent {
+                        entity,
+                        name: marker.name.clone(),
+                    });
+                }
+            }
+        }
+
+        if !clip.root_motion {
+            continue;
+        }
+        let Some(curve) = root_bone
+            .as_ref()
+            .and_then(|root_bone| root_translation_curve(animation_clip, &root_bone.0))
+        else {
+            continue;
+        };
+        let position = |time| root_position(curve, duration, state.repeats(), time);
+        let delta = position(elapsed) - position(previous);
+        // The vertical movement still comes from gravity and jumping.
+        let horizontal = transform.rotation * (Vec3::new(delta.x, 0.0, delta.z) * transform.scale);
+        let translation = character_controller.translation.get_or_insert(Vec3::ZERO);
+        translation.x = horizontal.x;
+        translation.z = horizontal.z;
+    }
+}
+
+/// Markers are meant for effects like footstep sounds, until then they show
+/// up in the debug log.
+fn log_animation_events(mut events: EventReader<AnimationEvent>) {
+    for event in events.iter() {
+        debug!("{:?} passed animation marker {}", event.entity, event.name);
+    }
+}
+
+/// Keeps root bones of clips with root motion in place horizontally, as the
+/// whole player is moved instead.
+fn pin_root_bones(
+    game_assets: Res<GameAssets>,
+    clips: Res<Assets<AnimationClip>>,
+    root_bone: Option<Res<RootBone>>,
+    animator_query: Query<&LocomotionAnimator>,
+    mut transform_query: Query<&mut Transform>,
+) {
+    let Some(root_bone) = root_bone else {
+        return;
+    };
+    for animator in animator_query.iter() {
+        let (Some(bone), Some(state)) = (animator.root_bone, animator.state) else {
+            continue;
+        };
+        let Some(clip) = game_assets.player_animations.get(state) else {
+            continue;
+        };
+        if !clip.root_motion {
+            continue;
+        }
+        let Some(start) = clips
+            .get(&clip.handle)
+            .and_then(|animation_clip| root_translation_curve(animation_clip, &root_bone.0))
+            .and_then(|curve| sample_translation(curve, 0.0))
+        else {
+            continue;
+        };
+        if let Ok(mut transform) = transform_query.get_mut(bone) {
+            transform.translation.x = start.x;
+            transform.translation.z = start.z;
+        }
+    }
+}
+
+fn root_translation_curve<'a>(
+    animation_clip: &'a AnimationClip,
+    root_bone: &str,
+) -> Option<&'a VariableCurve> {
+    animation_clip
+        .curves()
+        .iter()
+        .filter(|(path, _)| {
+            path.parts
+                .last()
+                .map(|name| name.as_str() == root_bone)
+                .unwrap_or(false)
+        })
+        .flat_map(|(_, curves)| curves.iter())
+        .find(|curve| matches!(curve.keyframes, Keyframes::Translation(_)))
+}
+
+/// Linearly interpolated translation of the curve, clamped to its first and
+/// last keyframe.
+fn sample_translation(curve: &VariableCurve, time: f32) -> Option<Vec3> {
+    let Keyframes::Translation(keyframes) = &curve.keyframes else {
+        return None;
+    };
+    let timestamps = &curve.keyframe_timestamps;
+    let index = timestamps.partition_point(|timestamp| *timestamp <= time);
+    if index == 0 {
+        return keyframes.first().copied();
+    }
+    if index >= timestamps.len() {
+        return keyframes.last().copied();
+    }
+    let (start, end) = (timestamps[index - 1], timestamps[index]);
+    let factor = (time - start) / (end - start);
+    Some(keyframes[index - 1].lerp(keyframes[index], factor))
+}
+
+/// Position of the root bone after playing for `elapsed` seconds, adding up
+/// the distance covered by every finished cycle of a looping clip.
+fn root_position(curve: &VariableCurve, duration: f32, repeats: bool, elapsed: f32) -> Vec3 {
+    let sample = |time| sample_translation(curve, time).unwrap_or_default();
+    if !repeats {
+        return sample(elapsed.min(duration));
+    }
+    let cycles = (elapsed / duration).floor();
+    sample(elapsed - cycles * duration) + (sample(duration) - sample(0.0)) * cycles
+}
+
+/// Transforms of `root` and all its descendants.
+fn pose(
+    root: Entity,
+    children_query: &Query<&Children>,
+    transform_query: &Query<&Transform>,
+) -> Vec<(Entity, Transform)> {
+    let mut pose = Vec::new();
+    let mut stack = vec![root];
+    while let Some(entity) = stack.pop() {
+        if let Ok(transform) = transform_query.get(entity) {
+            pose.push((entity, *transform));
+        }
+        if let Ok(children) = children_query.get(entity) {
+            stack.extend(children.iter().copied());
+        }
+    }
+    pose
+}
+
+fn apply_crossfade(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut crossfade_query: Query<(Entity, &mut Crossfade, &AnimationPlayer)>,
+    mut transform_query: Query<&mut Transform>,
+) {
+    for (entity, mut crossfade, animation_player) in crossfade_query.iter_mut() {
+        // Paused players don't write their pose, blending again would drift
+        // back to the old pose.
+        if animation_player.is_paused() {
+            continue;
+        }
+        crossfade.elapsed += time.delta_seconds();
+        let weight = (crossfade.elapsed / CROSSFADE_DURATION).min(1.0);
+        for (bone, from) in crossfade.from.iter() {
+            if let Ok(mut transform) = transform_query.get_mut(*bone) {
+                transform.translation = from.translation.lerp(transform.translation, weight);
+                transform.rotation = from.rotation.slerp(transform.rotation, weight);
+                transform.scale = from.scale.lerp(transform.scale, weight);
+            }
+        }
+        if weight >= 1.0 {
+            commands.entity(entity).remove::<Crossfade>();
+        }
+    }
+}
+
+fn pause_animations(mut animation_player_query: Query<&mut AnimationPlayer>) {
+    for mut animation_player in animation_player_query.iter_mut() {
+        animation_player.pause();
+    }
+}
+
+fn resume_animations(mut animation_player_query: Query<&mut AnimationPlayer>) {
+    for mut animation_player in animation_player_query.iter_mut() {
+        animation_player.resume();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use bevy::animation::EntityPath;
+    use bevy::asset::AssetPlugin;
+    use bevy::ecs::event::ManualEventReader;
+    use bevy::scene::ScenePlugin;
+
+    use super::*;
+
+    /// Player in the walk state with a one second clip on its animation
+    /// player, which moves the root bone 2 m forward per cycle.
+    fn app(events: Vec<AnimationMarker>, root_motion: bool) -> (App, Entity, Entity) {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<AnimationClip>()
+            .add_event::<AnimationEvent>()
+            .insert_resource(RootBone("Hips".to_string()))
+            .add_system(advance_clips);
+
+        let mut clip = AnimationClip::default();
+        clip.add_curve_to_path(
+            EntityPath {
+                parts: vec![Name::new("Armature"), Name::new("Hips")],
+            },
+            VariableCurve {
+                keyframe_timestamps: vec![0.0, 1.0],
+                keyframes: Keyframes::Translation(vec![
+                    Vec3::new(0.0, 1.0, 0.0),
+                    Vec3::new(0.0, 1.2, 2.0),
+                ]),
+            },
+        );
+        let handle = app.world.resource_mut::<Assets<AnimationClip>>().add(clip);
+        app.insert_resource(GameAssets {
+            player: Handle::default(),
+            tree: Handle::default(),
+            level: Handle::default(),
+            font: Handle::default(),
+            player_animations: LocomotionClips {
+                idle: None,
+                walk: Some(PlayerClip {
+                    handle: handle.clone(),
+                    events,
+                    root_motion,
+                }),
+                run: None,
+                jump: None,
+            },
+        });
+
+        let mut animation_player = AnimationPlayer::default();
+        animation_player.start(handle).repeat();
+        let animation_player = app.world.spawn(animation_player).id();
+        let player = app
+            .world
+            .spawn((
+                LocomotionAnimator {
+                    animation_player: Some(animation_player),
+                    root_bone: None,
+                    state: Some(Locomotion::Walk),
+                    last_elapsed: 0.0,
+                },
+                Transform::default(),
+                KinematicCharacterController::default(),
+            ))
+            .id();
+        (app, player, animation_player)
+    }
+
+    /// Moves the clip to `elapsed` like the animation player would and runs
+    /// one frame.
+    fn advance(app: &mut App, animation_player: Entity, elapsed: f32) {
+        app.world
+            .get_mut::<AnimationPlayer>(animation_player)
+            .unwrap()
+            .set_elapsed(elapsed);
+        app.update();
+    }
+
+    fn read_events(
+        app: &App,
+        reader: &mut ManualEventReader<AnimationEvent>,
+    ) -> Vec<(Entity, String)> {
+        reader
+            .iter(app.world.resource::<Events<AnimationEvent>>())
+            .map(|event| (event.entity, event.name.clone()))
+            .collect()
+    }
+
+    fn marker(time: f32, name: &str) -> AnimationMarker {
+        AnimationMarker {
+            time,
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn markers_fire_once_per_cycle() {
+        let (mut app, player, animation_player) =
+            app(vec![marker(0.25, "left"), marker(0.75, "right")], false);
+        let mut reader = ManualEventReader::default();
+
+        advance(&mut app, animation_player, 0.5);
+        assert_eq!(
+            read_events(&app, &mut reader),
+            vec![(player, "left".to_string())]
+        );
+
+        advance(&mut app, animation_player, 0.6);
+        assert!(read_events(&app, &mut reader).is_empty());
+
+        // Wraps around into the second cycle.
+        advance(&mut app, animation_player, 1.3);
+        assert_eq!(
+            read_events(&app, &mut reader),
+            vec![(player, "right".to_string()), (player, "left".to_string())]
+        );
+    }
+
+    #[test]
+    fn root_motion_moves_the_player() {
+        let (mut app, player, animation_player) = app(Vec::new(), true);
+        // Turned so the clip's forward `+Z` points along `+X`.
+        app.world.get_mut::<Transform>(player).unwrap().rotation = Quat::from_rotation_y(FRAC_PI_2);
+
+        let translation = |app: &mut App| {
+            app.world
+                .get_mut::<KinematicCharacterController>(player)
+                .unwrap()
+                .translation
+                .take()
+                .unwrap()
+        };
+
+        advance(&mut app, animation_player, 0.5);
+        let delta = translation(&mut app);
+        assert!(
+            delta.abs_diff_eq(Vec3::new(1.0, 0.0, 0.0), 1e-5),
+            "{}",
+            delta
+        );
+
+        // A full cycle covers the whole 2 m, the vertical bob is ignored.
+        advance(&mut app, animation_player, 1.25);
+        let delta = translation(&mut app);
+        assert!(
+            delta.abs_diff_eq(Vec3::new(1.5, 0.0, 0.0), 1e-5),
+            "{}",
+            delta
+        );
+    }
+
+    #[test]
+    fn clips_without_root_motion_leave_the_controller_alone() {
+        let (mut app, player, animation_player) = app(Vec::new(), false);
+        advance(&mut app, animation_player, 0.5);
+        let character_controller = app
+            .world
+            .get::<KinematicCharacterController>(player)
+            .unwrap();
+        assert_eq!(character_controller.translation, None);
+    }
+
+    #[test]
+    fn scenes_without_animation_player_get_one_at_the_clip_root() {
+        let (mut app, _, _) = app(Vec::new(), false);
+        app.add_plugin(ScenePlugin)
+            .add_system(find_animation_players);
+
+        // Like a model whose clips are in a separate file.
+        let mut world = World::new();
+        world.spawn(Name::new("Armature"));
+        let scene = app
+            .world
+            .resource_mut::<Assets<Scene>>()
+            .add(Scene::new(world));
+        let player = app
+            .world
+            .spawn((
+                LocomotionAnimator::default(),
+                SceneBundle { scene, ..default() },
+            ))
+            .id();
+
+        for _ in 0..10 {
+            app.update();
+        }
+        let animation_player = app
+            .world
+            .get::<LocomotionAnimator>(player)
+            .unwrap()
+            .animation_player
+            .unwrap();
+        assert_eq!(
+            app.world.get::<Name>(animation_player).unwrap().as_str(),
+            "Armature"
+        );
+        assert!(app.world.get::<AnimationPlayer>(animation_player).is_some());
+    }
+}
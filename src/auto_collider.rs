@@ -0,0 +1,120 @@
+use bevy::prelude::*;
+use bevy::render::mesh::VertexAttributeValues;
+use bevy::scene::SceneInstance;
+use bevy_rapier3d::prelude::*;
+use serde::Deserialize;
+
+/// How a collider is computed from a render mesh.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MeshColliderKind {
+    /// Exact triangle mesh, best for static props.
+    TriMesh,
+    /// Single convex hull around all vertices.
+    ConvexHull,
+    /// Approximation by several convex parts, slow to compute.
+    ConvexDecomposition,
+}
+impl MeshColliderKind {
+    /// Reads the collider kind from glTF node names ending with `_col`,
+    /// optionally followed by `_trimesh`, `_convex` or `_decomposition`.
+    ///
+    /// Returns `Some(None)` for a plain `_col` suffix, which uses the kind
+    /// configured on the prop.
+    fn from_node_name(name: &str) -> Option<Option<Self>> {
+        if name.ends_with("_col") {
+            Some(None)
+        } else if name.ends_with("_col_trimesh") {
+            Some(Some(MeshColliderKind::TriMesh))
+        } else if name.ends_with("_col_convex") {
+            Some(Some(MeshColliderKind::ConvexHull))
+        } else if name.ends_with("_col_decomposition") {
+            Some(Some(MeshColliderKind::ConvexDecomposition))
+        } else {
+            None
+        }
+    }
+
+    pub fn collider(self, mesh: &Mesh) -> Option<Collider> {
+        match self {
+            MeshColliderKind::TriMesh => {
+                Collider::from_bevy_mesh(mesh, &ComputedColliderShape::TriMesh)
+            }
+            MeshColliderKind::ConvexHull => match mesh.attribute(Mesh::ATTRIBUTE_POSITION)? {
+                VertexAttributeValues::Float32x3(positions) => {
+                    let points: Vec<Vec3> = positions.iter().copied().map(Vec3::from).collect();
+                    Collider::convex_hull(&points)
+                }
+                _ => None,
+            },
+            MeshColliderKind::ConvexDecomposition => Collider::from_bevy_mesh(
+                mesh,
+                &ComputedColliderShape::ConvexDecomposition(VHACDParameters::default()),
+            ),
+        }
+    }
+}
+
+/// Generates colliders from the meshes of the scene on this entity as soon
+/// as the scene finished spawning.
+///
+/// If the scene contains nodes following the `_col` naming convention, only
+/// those are used and they are hidden. Otherwise every mesh gets a collider.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct AutoCollider(pub MeshColliderKind);
+
+pub struct AutoColliderPlugin;
+impl Plugin for AutoColliderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(generate_auto_colliders);
+    }
+}
+
+fn generate_auto_colliders(
+    mut commands: Commands,
+    scene_spawner: Res<SceneSpawner>,
+    meshes: Res<Assets<Mesh>>,
+    auto_collider_query: Query<(Entity, &AutoCollider, &SceneInstance)>,
+    mesh_query: Query<(&Handle<Mesh>, Option<&Name>, Option<&Parent>)>,
+    name_query: Query<&Name>,
+) {
+    for (entity, auto_collider, scene_instance) in auto_collider_query.iter() {
+        if !scene_spawner.instance_is_ready(**scene_instance) {
+            continue;
+        }
+
+        // glTF meshes are spawned as children of their node, so the naming
+        // convention is checked on both.
+        let meshes_in_scene: Vec<_> = scene_spawner
+            .iter_instance_entities(**scene_instance)
+            .filter_map(|mesh_entity| {
+                let (mesh, name, parent) = mesh_query.get(mesh_entity).ok()?;
+                let node_name = parent.and_then(|parent| name_query.get(parent.get()).ok());
+                let convention = [name, node_name]
+                    .into_iter()
+                    .flatten()
+                    .find_map(|name| MeshColliderKind::from_node_name(name.as_str()));
+                Some((mesh_entity, mesh, convention))
+            })
+            .collect();
+        let has_collision_nodes = meshes_in_scene
+            .iter()
+            .any(|(_, _, convention)| convention.is_some());
+
+        for (mesh_entity, mesh, convention) in meshes_in_scene {
+            if has_collision_nodes && convention.is_none() {
+                continue;
+            }
+            let kind = convention.flatten().unwrap_or(auto_collider.0);
+            let Some(collider) = meshes.get(mesh).and_then(|mesh| kind.collider(mesh)) else {
+                warn!("Could not create a {:?} collider for a mesh", kind);
+                continue;
+            };
+            let mut mesh_commands = commands.entity(mesh_entity);
+            mesh_commands.insert(collider);
+            if has_collision_nodes {
+                mesh_commands.insert(Visibility { is_visible: false });
+            }
+        }
+        commands.entity(entity).remove::<AutoCollider>();
+    }
+}
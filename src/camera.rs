@@ -0,0 +1,355 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::actions::{Action, ActionState};
+
+#[derive(Reflect, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CameraMode {
+    /// Looks out of the head of the locked entity.
+    FirstPerson,
+    /// Orbits around the locked entity.
+    #[default]
+    Orbit,
+    /// Detached camera to fly through the level, mainly for debugging.
+    FreeFly,
+}
+impl CameraMode {
+    pub fn next(self) -> Self {
+        match self {
+            CameraMode::FirstPerson => CameraMode::Orbit,
+            CameraMode::Orbit => CameraMode::FreeFly,
+            CameraMode::FreeFly => CameraMode::FirstPerson,
+        }
+    }
+}
+
+/// User preferences for controlling the camera.
+#[derive(Resource, Reflect, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct CameraSettings {
+    /// Radians per pixel of mouse movement.
+    pub mouse_sensitivity: f32,
+    /// Zoom per line for mouse wheels which scroll in lines.
+    pub scroll_line_sensitivity: f32,
+    /// Zoom per pixel for trackpads and other devices which scroll in pixels.
+    pub scroll_pixel_sensitivity: f32,
+    pub invert_y: bool,
+}
+impl Default for CameraSettings {
+    fn default() -> Self {
+        CameraSettings {
+            mouse_sensitivity: 0.01,
+            scroll_line_sensitivity: 0.5,
+            scroll_pixel_sensitivity: 0.02,
+            invert_y: false,
+        }
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct CameraController {
+    pub mode: CameraMode,
+    pub rotation_y: f32,
+    pub rotation_x: f32,
+    pub min_rotation_x: f32,
+    pub max_rotation_x: f32,
+    /// Desired distance to the locked entity.
+    pub distance: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Distance actually used, shortened when something blocks the view.
+    pub current_distance: f32,
+    /// Radius of the sphere cast towards the camera to detect obstacles.
+    pub collision_radius: f32,
+    /// How fast the camera moves back out after an obstacle is gone.
+    pub collision_recovery_speed: f32,
+    /// Time constant in seconds of the lag when following the locked entity.
+    /// Zero follows without any lag.
+    pub position_damping: f32,
+    /// Time constant in seconds of the lag when turning towards the target.
+    pub rotation_damping: f32,
+    /// Seconds of the current movement the camera looks ahead of the target.
+    pub look_ahead: f32,
+    /// Jumps of the target larger than this snap the camera instead of
+    /// following smoothly.
+    pub teleport_distance: f32,
+    /// Smoothed point the camera orbits around.
+    pub focus: Vec3,
+    #[reflect(ignore)]
+    pub last_target: Option<Vec3>,
+    /// Name of the node inside the locked entity's scene used as the eye in
+    /// first person mode, like `Head` for rigged characters.
+    ///
+    /// `None` by default, as `human.glb` isn't rigged and has no head node,
+    /// so the player's first person camera always sits at `eye_height`.
+    pub head_bone: Option<String>,
+    /// Eye height above the locked entity without a head bone.
+    pub eye_height: f32,
+    #[reflect(ignore)]
+    pub head_entity: Option<Entity>,
+    pub fly_speed: f32,
+    pub fly_position: Vec3,
+    /// Not saved, as entity ids change between sessions.
+    #[reflect(ignore)]
+    pub lock_entity: Entity,
+}
+/// Locked to a placeholder entity, needed to create the component through
+/// reflection.
+impl Default for CameraController {
+    fn default() -> Self {
+        CameraController::new(Entity::from_raw(u32::MAX))
+    }
+}
+impl CameraController {
+    pub fn new(lock_entity: Entity) -> Self {
+        CameraController {
+            mode: CameraMode::default(),
+            rotation_y: 0.0,
+            rotation_x: 0.0,
+            min_rotation_x: -std::f32::consts::PI / 2.0 * 0.9,
+            max_rotation_x: std::f32::consts::PI / 2.0 * 0.9,
+            distance: 5.0,
+            min_distance: 2.0,
+            max_distance: 10.0,
+            current_distance: 5.0,
+            collision_radius: 0.2,
+            collision_recovery_speed: 5.0,
+            position_damping: 0.1,
+            rotation_damping: 0.05,
+            look_ahead: 0.3,
+            teleport_distance: 10.0,
+            focus: Vec3::ZERO,
+            last_target: None,
+            head_bone: None,
+            eye_height: 1.3,
+            head_entity: None,
+            fly_speed: 10.0,
+            fly_position: Vec3::ZERO,
+            lock_entity,
+        }
+    }
+
+    /// Unit vector pointing from the target towards the orbiting camera.
+    ///
+    /// The camera always looks along the opposite direction, also in first
+    /// person and free fly mode, so switching modes keeps the view direction.
+    pub fn direction(&self) -> Vec3 {
+        Vec3::new(
+            self.rotation_y.sin() * self.rotation_x.cos(),
+            self.rotation_x.sin(),
+            self.rotation_y.cos() * self.rotation_x.cos(),
+        )
+    }
+}
+
+pub fn camera_movement(
+    time: Res<Time>,
+    actions: Res<ActionState>,
+    camera_settings: Res<CameraSettings>,
+    mut camera_controller_query: Query<(&mut CameraController, &Transform)>,
+) {
+    if let Ok((mut camera_controller, camera_transform)) = camera_controller_query.get_single_mut()
+    {
+        if actions.just_pressed(Action::SwitchCameraMode) {
+            camera_controller.mode = camera_controller.mode.next();
+            if camera_controller.mode == CameraMode::FreeFly {
+                camera_controller.fly_position = camera_transform.translation;
+            }
+            camera_controller.last_target = None;
+        }
+
+        let pitch = if camera_settings.invert_y {
+            -actions.value(Action::LookPitch)
+        } else {
+            actions.value(Action::LookPitch)
+        };
+        camera_controller.rotation_x = (camera_controller.rotation_x + pitch).clamp(
+            camera_controller.min_rotation_x,
+            camera_controller.max_rotation_x,
+        );
+        camera_controller.rotation_y += actions.value(Action::LookYaw);
+
+        match camera_controller.mode {
+            CameraMode::Orbit => {
+                camera_controller.distance =
+                    (camera_controller.distance + actions.value(Action::Zoom)).clamp(
+                        camera_controller.min_distance,
+                        camera_controller.max_distance,
+                    );
+            }
+            CameraMode::FreeFly => {
+                // Jump and crouch fly up and down, sprint flies faster.
+                let forward = -camera_controller.direction();
+                let right = forward.cross(Vec3::Y).normalize_or_zero();
+                let mut vertical = 0.0;
+                if actions.pressed(Action::Jump) {
+                    vertical += 1.0;
+                }
+                if actions.pressed(Action::Crouch) {
+                    vertical -= 1.0;
+                }
+                let vector = (forward * actions.value(Action::MoveForward)
+                    + right * actions.value(Action::Strafe)
+                    + Vec3::Y * vertical)
+                    .clamp_length_max(1.0);
+                let speed = if actions.pressed(Action::Sprint) {
+                    camera_controller.fly_speed * 3.0
+                } else {
+                    camera_controller.fly_speed
+                };
+                camera_controller.fly_position += vector * speed * time.delta_seconds();
+            }
+            CameraMode::FirstPerson => {}
+        }
+    }
+}
+
+pub fn apply_camera_position(
+    time: Res<Time>,
+    rapier_context: Res<RapierContext>,
+    mut camera_query: Query<(&mut Transform, &mut CameraController)>,
+    entity_position_query: Query<(&Transform, Option<&Velocity>), Without<CameraController>>,
+    children_query: Query<&Children>,
+    name_query: Query<&Name>,
+    global_transform_query: Query<&GlobalTransform>,
+) {
+    if let Ok((mut camera_transform, mut camera_controller)) = camera_query.get_single_mut() {
+        let direction = camera_controller.direction();
+        match camera_controller.mode {
+            CameraMode::FreeFly => {
+                *camera_transform = Transform::from_translation(camera_controller.fly_position)
+                    .looking_at(camera_controller.fly_position - direction, Vec3::Y);
+                return;
+            }
+            CameraMode::FirstPerson => {
+                let head_entity = camera_controller
+                    .head_entity
+                    .filter(|head| global_transform_query.get(*head).is_ok())
+                    .or_else(|| {
+                        find_descendant_by_name(
+                            camera_controller.lock_entity,
+                            camera_controller.head_bone.as_deref()?,
+                            &children_query,
+                            &name_query,
+                        )
+                    });
+                camera_controller.head_entity = head_entity;
+                let eye = head_entity
+                    .and_then(|head| global_transform_query.get(head).ok())
+                    .map(|head_transform| head_transform.translation())
+                    .or_else(|| {
+                        entity_position_query
+                            .get(camera_controller.lock_entity)
+                            .ok()
+                            .map(|(transform, _)| {
+                                transform.translation + Vec3::Y * camera_controller.eye_height
+                            })
+                    });
+                if let Some(eye) = eye {
+                    *camera_transform =
+                        Transform::from_translation(eye).looking_at(eye - direction, Vec3::Y);
+                }
+                return;
+            }
+            CameraMode::Orbit => {}
+        }
+
+        if let Ok((look_at_transform, velocity)) =
+            entity_position_query.get(camera_controller.lock_entity)
+        {
+            let delta_seconds = time.delta_seconds();
+            let target = look_at_transform.translation;
+            let look_ahead = velocity
+                .map(|velocity| Vec3::new(velocity.linvel.x, 0.0, velocity.linvel.z))
+                .unwrap_or(Vec3::ZERO)
+                * camera_controller.look_ahead;
+            let teleported = camera_controller
+                .last_target
+                .map(|last_target| {
+                    last_target.distance(target) > camera_controller.teleport_distance
+                })
+                .unwrap_or(true);
+            camera_controller.last_target = Some(target);
+            camera_controller.focus = if teleported {
+                target + look_ahead
+            } else {
+                let factor = smoothing_factor(camera_controller.position_damping, delta_seconds);
+                camera_controller.focus.lerp(target + look_ahead, factor)
+            };
+            let focus = camera_controller.focus;
+
+            // Only static geometry blocks the view, so the player and other
+            // moving bodies never push the camera around.
+            let collision_shape = Collider::ball(camera_controller.collision_radius);
+            let distance = rapier_context
+                .cast_shape(
+                    focus,
+                    Quat::IDENTITY,
+                    direction,
+                    &collision_shape,
+                    camera_controller.distance,
+                    QueryFilter::only_fixed(),
+                )
+                .map(|(_, toi)| toi.toi)
+                .unwrap_or(camera_controller.distance);
+
+            // Pull in immediately to never show the inside of an obstacle,
+            // but ease back out to avoid popping.
+            camera_controller.current_distance = if distance < camera_controller.current_distance {
+                distance
+            } else {
+                let factor =
+                    1.0 - (-camera_controller.collision_recovery_speed * delta_seconds).exp();
+                camera_controller.current_distance
+                    + (distance - camera_controller.current_distance) * factor
+            };
+
+            let desired_transform =
+                Transform::from_translation(focus + direction * camera_controller.current_distance)
+                    .looking_at(focus, Vec3::Y);
+            camera_transform.translation = desired_transform.translation;
+            camera_transform.rotation = if teleported {
+                desired_transform.rotation
+            } else {
+                let factor = smoothing_factor(camera_controller.rotation_damping, delta_seconds);
+                camera_transform
+                    .rotation
+                    .slerp(desired_transform.rotation, factor)
+            };
+        }
+    }
+}
+
+/// Frame rate independent interpolation factor for a lag with the time
+/// constant `damping` in seconds.
+fn smoothing_factor(damping: f32, delta_seconds: f32) -> f32 {
+    if damping <= 0.0 {
+        1.0
+    } else {
+        1.0 - (-delta_seconds / damping).exp()
+    }
+}
+
+/// Searches the hierarchy below `entity` for an entity with the given name,
+/// like a bone of a spawned glTF scene.
+pub fn find_descendant_by_name(
+    entity: Entity,
+    name: &str,
+    children_query: &Query<&Children>,
+    name_query: &Query<&Name>,
+) -> Option<Entity> {
+    let children = children_query.get(entity).ok()?;
+    children.iter().find_map(|child| {
+        if name_query
+            .get(*child)
+            .map(|child_name| child_name.as_str() == name)
+            .unwrap_or(false)
+        {
+            Some(*child)
+        } else {
+            find_descendant_by_name(*child, name, children_query, name_query)
+        }
+    })
+}
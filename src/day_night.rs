@@ -0,0 +1,260 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use serde::Deserialize;
+
+use crate::ron_asset::RonResourcePlugin;
+use crate::settings::GraphicsSettings;
+use crate::{AppState, Player};
+
+/// Lighting at one hour of the day, the lighting in between is interpolated
+/// from the neighboring keys.
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct LightingKey {
+    pub hour: f32,
+    /// sRGB color.
+    pub ambient_color: [f32; 3],
+    /// Multiplies the brightness from the graphics settings.
+    pub ambient_brightness: f32,
+    /// sRGB color.
+    pub sun_color: [f32; 3],
+    /// In lux.
+    pub sun_illuminance: f32,
+}
+impl LightingKey {
+    fn lerp(&self, other: &LightingKey, t: f32) -> LightingKey {
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        let lerp_color =
+            |a: [f32; 3], b: [f32; 3]| [lerp(a[0], b[0]), lerp(a[1], b[1]), lerp(a[2], b[2])];
+        LightingKey {
+            hour: lerp(self.hour, other.hour),
+            ambient_color: lerp_color(self.ambient_color, other.ambient_color),
+            ambient_brightness: lerp(self.ambient_brightness, other.ambient_brightness),
+            sun_color: lerp_color(self.sun_color, other.sun_color),
+            sun_illuminance: lerp(self.sun_illuminance, other.sun_illuminance),
+        }
+    }
+}
+
+/// Tuning values for the day/night cycle, loaded from
+/// `assets/default.daynight.ron`.
+#[derive(Resource, Deserialize, TypeUuid, Clone, Debug)]
+#[uuid = "7d0b4c52-8f0e-4a43-9d7b-5f21c6e0a8d4"]
+#[serde(default)]
+pub struct DayNightSettings {
+    /// Real seconds a full day takes at a time scale of 1.
+    pub day_length: f32,
+    /// Hour the clock starts at, between 0 and 24.
+    pub start_hour: f32,
+    pub time_scale: f32,
+    pub paused: bool,
+    /// Angle in degrees by which the path of the sun is tilted away from
+    /// passing straight overhead.
+    pub sun_tilt: f32,
+    /// Half the size of the area around the player in which the sun casts
+    /// shadows.
+    pub shadow_distance: f32,
+    /// Keys sorted by hour, wrapping around at midnight.
+    ///
+    /// The sun should fade out towards sunrise and sunset, as it doesn't
+    /// shine at all while below the horizon.
+    pub gradient: Vec<LightingKey>,
+}
+impl Default for DayNightSettings {
+    fn default() -> Self {
+        let key =
+            |hour, ambient_color, ambient_brightness, sun_color, sun_illuminance| LightingKey {
+                hour,
+                ambient_color,
+                ambient_brightness,
+                sun_color,
+                sun_illuminance,
+            };
+        DayNightSettings {
+            day_length: 600.0,
+            start_hour: 8.0,
+            time_scale: 1.0,
+            paused: false,
+            sun_tilt: 30.0,
+            shadow_distance: 40.0,
+            gradient: vec![
+                key(0.0, [0.3, 0.35, 0.6], 0.3, [1.0, 1.0, 1.0], 0.0),
+                key(6.0, [1.0, 0.7, 0.5], 0.6, [1.0, 0.6, 0.4], 0.0),
+                key(7.0, [1.0, 0.8, 0.65], 0.7, [1.0, 0.7, 0.5], 6000.0),
+                key(12.0, [1.0, 1.0, 1.0], 1.0, [1.0, 0.97, 0.9], 20000.0),
+                key(17.0, [1.0, 0.8, 0.65], 0.7, [1.0, 0.7, 0.5], 6000.0),
+                key(18.0, [1.0, 0.7, 0.5], 0.6, [1.0, 0.6, 0.4], 0.0),
+            ],
+        }
+    }
+}
+impl DayNightSettings {
+    /// Interpolated lighting at `hour`, `None` without any keys.
+    fn lighting(&self, hour: f32) -> Option<LightingKey> {
+        let first = self.gradient.first()?;
+        let last = self.gradient.last()?;
+        let (from, to) = match self.gradient.iter().position(|key| key.hour > hour) {
+            Some(0) | None => (last, first),
+            Some(index) => (&self.gradient[index - 1], &self.gradient[index]),
+        };
+        let span = (to.hour - from.hour).rem_euclid(24.0);
+        let t = if span > 0.0 {
+            (hour - from.hour).rem_euclid(24.0) / span
+        } else {
+            0.0
+        };
+        Some(from.lerp(to, t))
+    }
+}
+
+/// Current time of the day/night cycle.
+///
+/// The time scale and pause flag start out from the [`DayNightSettings`] and
+/// can be changed at runtime for debugging.
+#[derive(Resource, Reflect, Debug)]
+pub struct TimeOfDay {
+    /// Between 0 and 24, the sun rises at 6 and sets at 18.
+    pub hour: f32,
+    pub time_scale: f32,
+    pub paused: bool,
+}
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        let settings = DayNightSettings::default();
+        TimeOfDay {
+            hour: settings.start_hour,
+            time_scale: settings.time_scale,
+            paused: settings.paused,
+        }
+    }
+}
+
+/// The directional light which moves with the time of day.
+#[derive(Component)]
+pub struct Sun;
+
+pub struct DayNightPlugin;
+impl Plugin for DayNightPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(RonResourcePlugin::<DayNightSettings>::new(
+            "default.daynight.ron",
+            &["daynight.ron"],
+        ))
+        .init_resource::<TimeOfDay>()
+        .register_type::<TimeOfDay>()
+        .add_startup_system(spawn_sun)
+        .add_system(apply_day_night_settings)
+        .add_system(update_lighting.after(apply_day_night_settings))
+        .add_system_set(SystemSet::on_update(AppState::InGame).with_system(advance_time_of_day));
+    }
+}
+
+fn spawn_sun(mut commands: Commands) {
+    commands.spawn((
+        DirectionalLightBundle {
+            directional_light: DirectionalLight {
+                shadows_enabled: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Sun,
+    ));
+}
+
+/// Applies the settings file to the clock once it's loaded and after hot
+/// reloads.
+///
+/// The clock only restarts at `start_hour` when the file is first loaded,
+/// while changes to the time scale and pause flag apply right away.
+fn apply_day_night_settings(
+    mut settings: ResMut<DayNightSettings>,
+    mut time_of_day: ResMut<TimeOfDay>,
+    mut loaded: Local<bool>,
+) {
+    // The defaults the resource starts out with aren't from the file.
+    if !settings.is_changed() || settings.is_added() {
+        return;
+    }
+    // Sorting doesn't count as another change, which would run this again.
+    settings
+        .bypass_change_detection()
+        .gradient
+        .sort_by(|a, b| a.hour.total_cmp(&b.hour));
+    if !*loaded {
+        time_of_day.hour = settings.start_hour.rem_euclid(24.0);
+        *loaded = true;
+    }
+    time_of_day.time_scale = settings.time_scale;
+    time_of_day.paused = settings.paused;
+}
+
+fn advance_time_of_day(
+    time: Res<Time>,
+    settings: Res<DayNightSettings>,
+    mut time_of_day: ResMut<TimeOfDay>,
+) {
+    if time_of_day.paused || settings.day_length <= 0.0 {
+        return;
+    }
+    let hours = time.delta_seconds() * time_of_day.time_scale * 24.0 / settings.day_length;
+    time_of_day.hour = (time_of_day.hour + hours).rem_euclid(24.0);
+}
+
+/// Moves the sun along its path and sets the sun and ambient light from the
+/// gradient.
+fn update_lighting(
+    settings: Res<DayNightSettings>,
+    graphics_settings: Res<GraphicsSettings>,
+    time_of_day: Res<TimeOfDay>,
+    mut ambient_light: ResMut<AmbientLight>,
+    mut sun_query: Query<(&mut Transform, &mut DirectionalLight), With<Sun>>,
+    player_query: Query<&Transform, (With<Player>, Without<Sun>)>,
+) {
+    let Ok((mut sun_transform, mut sun)) = sun_query.get_single_mut() else {
+        return;
+    };
+
+    // Shadows only cover an area around the light's position.
+    if let Ok(player_transform) = player_query.get_single() {
+        sun_transform.translation = player_transform.translation;
+    }
+    if settings.is_changed() {
+        let distance = settings.shadow_distance;
+        sun.shadow_projection = OrthographicProjection {
+            left: -distance,
+            right: distance,
+            bottom: -distance,
+            top: distance,
+            near: -10.0 * distance,
+            far: 10.0 * distance,
+            ..Default::default()
+        };
+    }
+
+    if !time_of_day.is_changed() && !settings.is_changed() && !graphics_settings.is_changed() {
+        return;
+    }
+    // Rises along +X at 6, is highest at 12 and sets along -X at 18. The
+    // light starts out shining along -X and is raised around Z.
+    let elevation = (time_of_day.hour - 6.0) / 12.0 * PI;
+    sun_transform.rotation = Quat::from_rotation_x(settings.sun_tilt.to_radians())
+        * Quat::from_rotation_z(elevation)
+        * Quat::from_rotation_y(FRAC_PI_2);
+
+    let Some(lighting) = settings.lighting(time_of_day.hour) else {
+        return;
+    };
+    let [red, green, blue] = lighting.sun_color;
+    sun.color = Color::rgb(red, green, blue);
+    // Below the horizon it would light the ground from below.
+    sun.illuminance = if (0.0..=PI).contains(&elevation) {
+        lighting.sun_illuminance
+    } else {
+        0.0
+    };
+    let [red, green, blue] = lighting.ambient_color;
+    ambient_light.color = Color::rgb(red, green, blue);
+    ambient_light.brightness = lighting.ambient_brightness * graphics_settings.ambient_brightness;
+}
@@ -0,0 +1,269 @@
+use bevy::app::AppExit;
+use bevy::asset::{HandleId, LoadState};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use serde::Deserialize;
+
+use crate::animation::{ClipSettings, LocomotionClips, PlayerClip, RootBone};
+use crate::level::Level;
+use crate::ron_asset::RonAssetLoader;
+use crate::scene_import::SceneImportRules;
+use crate::AppState;
+
+/// Asset paths of everything in [`GameAssets`], loaded from
+/// `assets/game.manifest.ron`.
+#[derive(Deserialize, TypeUuid, Debug)]
+#[uuid = "a802516e-6483-4b40-9181-1d814a099c24"]
+pub struct AssetManifest {
+    pub player: String,
+    pub tree: String,
+    pub level: String,
+    pub font: String,
+    #[serde(default)]
+    pub player_animations: LocomotionClips<ClipSettings>,
+    /// Required by clips with root motion.
+    #[serde(default)]
+    pub player_root_bone: Option<RootBone>,
+    #[serde(default)]
+    pub scene_imports: SceneImportRules,
+}
+
+#[derive(Resource)]
+pub struct GameAssets {
+    pub player: Handle<Scene>,
+    pub tree: Handle<Scene>,
+    pub level: Handle<Level>,
+    pub font: Handle<Font>,
+    pub player_animations: LocomotionClips<PlayerClip>,
+}
+impl GameAssets {
+    fn load(manifest: &AssetManifest, asset_server: &AssetServer) -> Self {
+        GameAssets {
+            player: asset_server.load(manifest.player.as_str()),
+            tree: asset_server.load(manifest.tree.as_str()),
+            level: asset_server.load(manifest.level.as_str()),
+            font: asset_server.load(manifest.font.as_str()),
+            player_animations: manifest
+                .player_animations
+                .map(|clip| PlayerClip::load(clip, asset_server)),
+        }
+    }
+
+    fn handle_ids(&self) -> Vec<HandleId> {
+        let mut ids = vec![
+            self.player.id(),
+            self.tree.id(),
+            self.level.id(),
+            self.font.id(),
+        ];
+        ids.extend(self.player_animations.iter().map(|clip| clip.handle.id()));
+        ids
+    }
+
+    /// A source file can load fine but still miss the labeled asset, like a
+    /// `#Scene1` which doesn't exist in the glTF file.
+    fn missing_ids(
+        &self,
+        scenes: &Assets<Scene>,
+        levels: &Assets<Level>,
+        fonts: &Assets<Font>,
+        clips: &Assets<AnimationClip>,
+    ) -> Vec<HandleId> {
+        let mut missing = Vec::new();
+        for scene in [&self.player, &self.tree] {
+            if !scenes.contains(scene) {
+                missing.push(scene.id());
+            }
+        }
+        if !levels.contains(&self.level) {
+            missing.push(self.level.id());
+        }
+        if !fonts.contains(&self.font) {
+            missing.push(self.font.id());
+        }
+        for clip in self.player_animations.iter() {
+            if !clips.contains(&clip.handle) {
+                missing.push(clip.handle.id());
+            }
+        }
+        missing
+    }
+}
+
+/// Font of the loading screen, which is shown before the manifest with the
+/// game font is loaded.
+const LOADING_FONT: &str = "fonts/DejaVuSans.ttf";
+
+#[derive(Resource)]
+struct AssetManifestHandle(Handle<AssetManifest>);
+
+/// Assets which failed to load, the game stays on the loading screen and
+/// shows them.
+#[derive(Resource, Default)]
+struct LoadingErrors(Vec<String>);
+
+#[derive(Component)]
+struct LoadingScreen;
+
+#[derive(Component)]
+struct LoadingText;
+
+pub struct GameAssetsPlugin;
+impl Plugin for GameAssetsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<AssetManifest>()
+            .add_asset_loader(RonAssetLoader::<AssetManifest>::new(&["manifest.ron"]))
+            .init_resource::<LoadingErrors>()
+            .add_startup_system(load_asset_manifest)
+            .add_system_set(
+                SystemSet::on_enter(AppState::Loading).with_system(spawn_loading_screen),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Loading)
+                    .with_system(check_loading_progress)
+                    .with_system(show_loading_errors.after(check_loading_progress))
+                    .with_system(quit_after_loading_failure),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Loading).with_system(despawn_loading_screen),
+            );
+    }
+}
+
+fn load_asset_manifest(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(AssetManifestHandle(asset_server.load("game.manifest.ron")));
+}
+
+fn spawn_loading_screen(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((Camera2dBundle::default(), LoadingScreen));
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            LoadingScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "Loading...",
+                    TextStyle {
+                        font: asset_server.load(LOADING_FONT),
+                        font_size: 32.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                LoadingText,
+            ));
+        });
+}
+
+fn despawn_loading_screen(
+    mut commands: Commands,
+    loading_screen_query: Query<Entity, With<LoadingScreen>>,
+) {
+    for entity in loading_screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn show_loading_errors(
+    errors: Res<LoadingErrors>,
+    mut text_query: Query<&mut Text, With<LoadingText>>,
+) {
+    if !errors.is_changed() || errors.0.is_empty() {
+        return;
+    }
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = format!(
+            "Could not load the game:\n{}\n\nPress Esc to quit",
+            errors.0.join("\n")
+        );
+        text.sections[0].style.color = Color::rgb(1.0, 0.5, 0.5);
+    }
+}
+
+/// Raw keys, as the input bindings might be what failed to load.
+fn quit_after_loading_failure(
+    errors: Res<LoadingErrors>,
+    keys: Res<Input<KeyCode>>,
+    mut app_exit_events: EventWriter<AppExit>,
+) {
+    if !errors.0.is_empty() && keys.just_pressed(KeyCode::Escape) {
+        app_exit_events.send(AppExit);
+    }
+}
+
+/// Fills [`GameAssets`] from the manifest and switches to
+/// [`AppState::MainMenu`] as soon as everything is loaded.
+///
+/// Failed assets are collected in [`LoadingErrors`] instead.
+fn check_loading_progress(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    manifest_handle: Res<AssetManifestHandle>,
+    manifests: Res<Assets<AssetManifest>>,
+    scenes: Res<Assets<Scene>>,
+    levels: Res<Assets<Level>>,
+    fonts: Res<Assets<Font>>,
+    clips: Res<Assets<AnimationClip>>,
+    game_assets: Option<Res<GameAssets>>,
+    mut app_state: ResMut<State<AppState>>,
+    mut errors: ResMut<LoadingErrors>,
+) {
+    if !errors.0.is_empty() {
+        return;
+    }
+    let Some(game_assets) = game_assets else {
+        match asset_server.get_load_state(&manifest_handle.0) {
+            LoadState::Loaded => {
+                if let Some(manifest) = manifests.get(&manifest_handle.0) {
+                    commands.insert_resource(GameAssets::load(manifest, &asset_server));
+                    commands.insert_resource(manifest.scene_imports.clone());
+                    if let Some(root_bone) = &manifest.player_root_bone {
+                        commands.insert_resource(root_bone.clone());
+                    }
+                }
+            }
+            LoadState::Failed => {
+                error!("Failed to load the asset manifest game.manifest.ron");
+                errors.0.push("game.manifest.ron".to_string());
+            }
+            _ => {}
+        }
+        return;
+    };
+
+    let failed_ids: Vec<HandleId> =
+        match asset_server.get_group_load_state(game_assets.handle_ids()) {
+            LoadState::Loaded => game_assets.missing_ids(&scenes, &levels, &fonts, &clips),
+            LoadState::Failed => game_assets
+                .handle_ids()
+                .into_iter()
+                .filter(|id| asset_server.get_load_state(*id) == LoadState::Failed)
+                .collect(),
+            _ => return,
+        };
+    if failed_ids.is_empty() {
+        // Retried next frame if another transition is already queued.
+        let _ = app_state.set(AppState::MainMenu);
+    } else {
+        for id in failed_ids {
+            let name = match asset_server.get_handle_path(id) {
+                Some(path) => match path.label() {
+                    Some(label) => format!("{}#{}", path.path().display(), label),
+                    None => path.path().display().to_string(),
+                },
+                None => format!("{:?}", id),
+            };
+            error!("Failed to load game asset {}", name);
+            errors.0.push(name);
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::Deserialize;
+
+use crate::actions::{Action, ActionState, InputBindings, InputSource};
+use crate::camera::{CameraController, CameraMode};
+use crate::game_assets::GameAssets;
+use crate::{AppState, Player};
+
+/// Interactables further away than this aren't found, even with a larger
+/// `radius`.
+const MAX_INTERACTION_RADIUS: f32 = 5.0;
+
+/// Something the player can use with [`Action::Interact`] when within
+/// `radius` of it.
+///
+/// Colliders on the entity or any of its descendants make it a target, like
+/// the meshes of a scene with an
+/// [`AutoCollider`](crate::auto_collider::AutoCollider).
+#[derive(Component, Deserialize, Clone, Debug)]
+pub struct Interactable {
+    /// Shown while the entity is targeted, like `Chop tree`.
+    pub prompt: String,
+    pub radius: f32,
+}
+
+/// Sent when the player uses the targeted [`Interactable`].
+pub struct InteractionEvent {
+    pub target: Entity,
+}
+
+/// The [`Interactable`] which is used by the next [`Action::Interact`].
+#[derive(Resource, Default)]
+pub struct InteractionTarget(pub Option<Entity>);
+
+/// Root of the prompt UI, which is hidden without a target.
+#[derive(Component)]
+struct InteractionPrompt;
+
+#[derive(Component)]
+struct InteractionPromptText;
+
+pub struct InteractionPlugin;
+impl Plugin for InteractionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<InteractionEvent>()
+            .init_resource::<InteractionTarget>()
+            .add_system_set(
+                SystemSet::on_enter(AppState::InGame).with_system(spawn_interaction_prompt),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(find_interaction_target)
+                    .with_system(interact.after(find_interaction_target))
+                    .with_system(update_interaction_prompt.after(find_interaction_target)),
+            )
+            .add_system_set(
+                SystemSet::on_pause(AppState::InGame).with_system(hide_interaction_prompt),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::InGame)
+                    .with_system(clear_interaction_target)
+                    .with_system(despawn_interaction_prompt),
+            );
+    }
+}
+
+/// Targets the interactable the camera looks at, or else the one in reach
+/// which is closest to the view direction.
+fn find_interaction_target(
+    rapier_context: Res<RapierContext>,
+    mut target: ResMut<InteractionTarget>,
+    camera_query: Query<(&CameraController, &GlobalTransform)>,
+    player_query: Query<(Entity, &GlobalTransform), With<Player>>,
+    interactable_query: Query<(&Interactable, &GlobalTransform)>,
+    parent_query: Query<&Parent>,
+) {
+    target.0 = None;
+    let (Ok((camera_controller, camera_transform)), Ok((player, player_transform))) =
+        (camera_query.get_single(), player_query.get_single())
+    else {
+        return;
+    };
+    if camera_controller.mode == CameraMode::FreeFly {
+        return;
+    }
+
+    let player_position = player_transform.translation();
+    let in_reach = |entity: Entity| {
+        interactable_query
+            .get(entity)
+            .map(|(interactable, transform)| {
+                transform.translation().distance(player_position)
+                    <= interactable.radius.min(MAX_INTERACTION_RADIUS)
+            })
+            .unwrap_or(false)
+    };
+    // Colliders are often on child entities, like the meshes of a scene.
+    let find_interactable = |collider: Entity| {
+        let mut entity = collider;
+        loop {
+            if interactable_query.contains(entity) {
+                return Some(entity);
+            }
+            entity = parent_query.get(entity).ok()?.get();
+        }
+    };
+    let filter = QueryFilter::default().exclude_collider(player);
+
+    // The camera orbits behind the player, so the ray has to reach past it.
+    let view_direction = camera_transform.forward();
+    let max_distance =
+        camera_transform.translation().distance(player_position) + MAX_INTERACTION_RADIUS;
+    if let Some((collider, _)) = rapier_context.cast_ray(
+        camera_transform.translation(),
+        view_direction,
+        max_distance,
+        true,
+        filter,
+    ) {
+        if let Some(entity) = find_interactable(collider).filter(|entity| in_reach(*entity)) {
+            target.0 = Some(entity);
+            return;
+        }
+    }
+
+    let mut best: Option<(Entity, f32)> = None;
+    rapier_context.intersections_with_shape(
+        player_position,
+        Quat::IDENTITY,
+        &Collider::ball(MAX_INTERACTION_RADIUS),
+        filter,
+        |collider| {
+            let Some(entity) = find_interactable(collider).filter(|entity| in_reach(*entity))
+            else {
+                return true;
+            };
+            let Ok((_, transform)) = interactable_query.get(entity) else {
+                return true;
+            };
+            let facing = (transform.translation() - player_position)
+                .normalize_or_zero()
+                .dot(view_direction);
+            if best
+                .map(|(_, best_facing)| facing > best_facing)
+                .unwrap_or(true)
+            {
+                best = Some((entity, facing));
+            }
+            true
+        },
+    );
+    target.0 = best.map(|(entity, _)| entity);
+}
+
+fn interact(
+    actions: Res<ActionState>,
+    target: Res<InteractionTarget>,
+    mut events: EventWriter<InteractionEvent>,
+) {
+    if !actions.just_pressed(Action::Interact) {
+        return;
+    }
+    if let Some(target) = target.0 {
+        events.send(InteractionEvent { target });
+    }
+}
+
+fn spawn_interaction_prompt(mut commands: Commands, game_assets: Res<GameAssets>) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    display: Display::None,
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        bottom: Val::Px(96.0),
+                        ..Default::default()
+                    },
+                    size: Size::new(Val::Percent(100.0), Val::Auto),
+                    justify_content: JustifyContent::Center,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            InteractionPrompt,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: game_assets.font.clone(),
+                        font_size: 28.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                InteractionPromptText,
+            ));
+        });
+}
+
+/// The prompt would otherwise show over the pause menu, it's shown again by
+/// [`update_interaction_prompt`] on resume.
+fn hide_interaction_prompt(mut prompt_query: Query<&mut Style, With<InteractionPrompt>>) {
+    for mut style in prompt_query.iter_mut() {
+        style.display = Display::None;
+    }
+}
+
+/// Keeps a despawned level's entity from being used after returning in game.
+fn clear_interaction_target(mut target: ResMut<InteractionTarget>) {
+    target.0 = None;
+}
+
+fn despawn_interaction_prompt(
+    mut commands: Commands,
+    prompt_query: Query<Entity, With<InteractionPrompt>>,
+) {
+    for entity in prompt_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Shows the prompt of the target together with the first key bound to
+/// [`Action::Interact`].
+fn update_interaction_prompt(
+    target: Res<InteractionTarget>,
+    bindings: Res<InputBindings>,
+    interactable_query: Query<&Interactable>,
+    mut prompt_query: Query<&mut Style, With<InteractionPrompt>>,
+    mut text_query: Query<&mut Text, With<InteractionPromptText>>,
+) {
+    let (Ok(mut style), Ok(mut text)) =
+        (prompt_query.get_single_mut(), text_query.get_single_mut())
+    else {
+        return;
+    };
+    // Only write on changes, which would relayout the UI every frame.
+    let Some(interactable) = target
+        .0
+        .and_then(|entity| interactable_query.get(entity).ok())
+    else {
+        if style.display != Display::None {
+            style.display = Display::None;
+        }
+        return;
+    };
+    let key = bindings
+        .bindings(Action::Interact)
+        .iter()
+        .find_map(|binding| match binding.source {
+            InputSource::Key(key) => Some(format!("[{:?}] ", key)),
+            _ => None,
+        })
+        .unwrap_or_default();
+    let value = format!("{}{}", key, interactable.prompt);
+    if text.sections[0].value != value {
+        text.sections[0].value = value;
+    }
+    if style.display != Display::Flex {
+        style.display = Display::Flex;
+    }
+}
@@ -0,0 +1,357 @@
+use bevy::ecs::system::CommandQueue;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy_rapier3d::prelude::*;
+use serde::Deserialize;
+
+use crate::auto_collider::{AutoCollider, MeshColliderKind};
+use crate::camera::CameraController;
+use crate::game_assets::GameAssets;
+use crate::ron_asset::RonAssetLoader;
+use crate::save::{RemovedSaveKeys, SaveKey, SavedEntitySpawner};
+use crate::scatter::{spawn_tree, ScatterRegion};
+use crate::streaming::{respawn_saved_tree, WorldStreaming};
+use crate::terrain::{spawn_terrain, Terrain};
+use crate::{AppState, PlayerBundle};
+
+/// Level description, loaded from `*.level.ron` files.
+///
+/// Plain `.ron` files can't be levels, as the other RON assets are told apart
+/// by their compound extensions, see [`RonAssetLoader`].
+#[derive(Deserialize, TypeUuid, Debug)]
+#[uuid = "bec06769-3949-47ca-b533-aed33f549628"]
+pub struct Level {
+    #[serde(default)]
+    pub spawn_points: Vec<SpawnPoint>,
+    #[serde(default)]
+    pub props: Vec<Prop>,
+    #[serde(default)]
+    pub colliders: Vec<StaticCollider>,
+    /// Regions filled with procedurally placed trees.
+    #[serde(default)]
+    pub scatter: Vec<ScatterRegion>,
+    #[serde(default)]
+    pub terrain: Option<Terrain>,
+    /// Endless procedural world which is loaded in chunks around the player.
+    #[serde(default)]
+    pub streaming: Option<WorldStreaming>,
+}
+impl Level {
+    pub fn spawn_point(&self, name: &str) -> Option<&SpawnPoint> {
+        self.spawn_points
+            .iter()
+            .find(|spawn_point| spawn_point.name == name)
+    }
+
+    /// Transforms of the trees in `region`, placed on the terrain if there
+    /// is one.
+    fn scatter_placements(&self, region: &ScatterRegion) -> Vec<Transform> {
+        let spawn_points: Vec<Vec3> = self
+            .spawn_points
+            .iter()
+            .map(|spawn_point| spawn_point.translation)
+            .collect();
+        let mut placements = region.placements(&spawn_points);
+        if let Some(terrain) = &self.terrain {
+            for transform in placements.iter_mut() {
+                if let Some(height) =
+                    terrain.procedural_height(transform.translation.x, transform.translation.z)
+                {
+                    transform.translation.y = height;
+                }
+            }
+        }
+        placements
+    }
+}
+
+/// Builds the transform of a placed level object. `yaw` is the rotation
+/// around the up axis in degrees.
+fn placement(translation: Vec3, yaw: f32, scale: f32) -> Transform {
+    Transform::from_translation(translation)
+        .with_rotation(Quat::from_rotation_y(yaw.to_radians()))
+        .with_scale(Vec3::splat(scale))
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SpawnPoint {
+    pub name: String,
+    pub translation: Vec3,
+    #[serde(default)]
+    pub yaw: f32,
+}
+impl SpawnPoint {
+    pub fn transform(&self) -> Transform {
+        placement(self.translation, self.yaw, 1.0)
+    }
+}
+
+/// A placed glTF scene, optionally with a collider.
+///
+/// `collider` adds a primitive shape, `auto_collider` generates colliders
+/// from the meshes of the scene.
+#[derive(Deserialize, Debug)]
+pub struct Prop {
+    /// Unique name which matches the prop with its state in saves, so it has
+    /// to stay the same when the level is edited.
+    pub id: String,
+    /// Asset path of the scene, like `tree.glb#Scene0`.
+    pub scene: String,
+    pub translation: Vec3,
+    #[serde(default)]
+    pub yaw: f32,
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    #[serde(default)]
+    pub collider: Option<ColliderShape>,
+    #[serde(default)]
+    pub auto_collider: Option<MeshColliderKind>,
+}
+impl Prop {
+    pub fn transform(&self) -> Transform {
+        placement(self.translation, self.yaw, self.scale)
+    }
+
+    fn save_key(&self) -> SaveKey {
+        SaveKey(format!("prop:{}", self.id))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct StaticCollider {
+    pub shape: ColliderShape,
+    pub translation: Vec3,
+    #[serde(default)]
+    pub yaw: f32,
+}
+impl StaticCollider {
+    pub fn transform(&self) -> Transform {
+        placement(self.translation, self.yaw, 1.0)
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub enum ColliderShape {
+    Cuboid { half_extents: Vec3 },
+    Ball { radius: f32 },
+    Capsule { half_height: f32, radius: f32 },
+}
+impl ColliderShape {
+    pub fn collider(&self) -> Collider {
+        match *self {
+            ColliderShape::Cuboid { half_extents } => {
+                Collider::cuboid(half_extents.x, half_extents.y, half_extents.z)
+            }
+            ColliderShape::Ball { radius } => Collider::ball(radius),
+            ColliderShape::Capsule {
+                half_height,
+                radius,
+            } => Collider::capsule_y(half_height, radius),
+        }
+    }
+}
+
+/// Marks everything spawned from a level, so it can be removed again.
+#[derive(Component, Reflect, Default)]
+pub struct LevelEntity;
+
+pub struct LevelPlugin;
+impl Plugin for LevelPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Level>()
+            .add_asset_loader(RonAssetLoader::<Level>::new(&["level.ron"]))
+            .register_type::<LevelEntity>()
+            .insert_resource(SavedEntitySpawner(respawn_saved))
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(spawn_level))
+            .add_system_set(SystemSet::on_update(AppState::InGame).with_system(reload_level))
+            .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(despawn_level));
+    }
+}
+
+fn spawn_level(
+    mut commands: Commands,
+    levels: Res<Assets<Level>>,
+    asset_server: Res<AssetServer>,
+    game_assets: Res<GameAssets>,
+    level_entities: Query<Entity, With<LevelEntity>>,
+) {
+    if let Some(level) = levels.get(&game_assets.level) {
+        respawn_level(
+            &mut commands,
+            level,
+            &asset_server,
+            &game_assets,
+            &level_entities,
+        );
+    }
+}
+
+/// Respawns the level when its file changes.
+fn reload_level(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<Level>>,
+    levels: Res<Assets<Level>>,
+    asset_server: Res<AssetServer>,
+    game_assets: Res<GameAssets>,
+    level_entities: Query<Entity, With<LevelEntity>>,
+) {
+    let modified = events.iter().any(
+        |event| matches!(event, AssetEvent::Modified { handle } if *handle == game_assets.level),
+    );
+    if !modified {
+        return;
+    }
+    if let Some(level) = levels.get(&game_assets.level) {
+        respawn_level(
+            &mut commands,
+            level,
+            &asset_server,
+            &game_assets,
+            &level_entities,
+        );
+    }
+}
+
+fn despawn_level(mut commands: Commands, level_entities: Query<Entity, With<LevelEntity>>) {
+    for entity in level_entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<WorldStreaming>();
+}
+
+fn respawn_level(
+    commands: &mut Commands,
+    level: &Level,
+    asset_server: &AssetServer,
+    game_assets: &GameAssets,
+    level_entities: &Query<Entity, With<LevelEntity>>,
+) {
+    for entity in level_entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    for collider in level.colliders.iter() {
+        commands.spawn((
+            RigidBody::Fixed,
+            collider.shape.collider(),
+            TransformBundle::from_transform(collider.transform()),
+            LevelEntity,
+        ));
+    }
+
+    if let Some(terrain) = &level.terrain {
+        let entity = spawn_terrain(commands, terrain, asset_server);
+        commands.entity(entity).insert(LevelEntity);
+    }
+
+    for prop in level.props.iter() {
+        spawn_prop(commands, prop, asset_server);
+    }
+
+    match &level.streaming {
+        Some(streaming) => commands.insert_resource(WorldStreaming {
+            spawn_points: level
+                .spawn_points
+                .iter()
+                .map(|spawn_point| spawn_point.translation)
+                .collect(),
+            ..streaming.clone()
+        }),
+        None => commands.remove_resource::<WorldStreaming>(),
+    }
+    for region in level.scatter.iter() {
+        for (index, transform) in level.scatter_placements(region).into_iter().enumerate() {
+            spawn_scatter_tree(commands, region, index, transform, game_assets);
+        }
+    }
+    commands.insert_resource(RemovedSaveKeys::default());
+
+    let player_transform = level
+        .spawn_point("player")
+        .map(SpawnPoint::transform)
+        .unwrap_or_default();
+    let player = commands
+        .spawn((
+            PlayerBundle::new(game_assets, player_transform),
+            SaveKey("player".to_string()),
+            LevelEntity,
+        ))
+        .id();
+    commands.spawn((
+        Camera3dBundle {
+            transform: Transform::from_xyz(5.0, 5.0, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
+            ..Default::default()
+        },
+        CameraController::new(player),
+        SaveKey("camera".to_string()),
+        LevelEntity,
+    ));
+}
+
+fn spawn_prop(commands: &mut Commands, prop: &Prop, asset_server: &AssetServer) -> Entity {
+    let mut entity = commands.spawn((
+        SceneBundle {
+            scene: asset_server.load(prop.scene.as_str()),
+            transform: prop.transform(),
+            ..Default::default()
+        },
+        prop.save_key(),
+        LevelEntity,
+    ));
+    if let Some(shape) = prop.collider {
+        entity.insert((RigidBody::Fixed, shape.collider()));
+    }
+    if let Some(kind) = prop.auto_collider {
+        entity.insert(AutoCollider(kind));
+    }
+    entity.id()
+}
+
+fn spawn_scatter_tree(
+    commands: &mut Commands,
+    region: &ScatterRegion,
+    index: usize,
+    transform: Transform,
+    game_assets: &GameAssets,
+) -> Entity {
+    let entity = spawn_tree(
+        commands,
+        game_assets.tree.clone(),
+        &region.trees,
+        transform,
+        SaveKey(format!("scatter:{}:{}", region.id, index)),
+    );
+    commands.entity(entity).insert(LevelEntity);
+    entity
+}
+
+/// Spawns the prop, scattered tree or streamed tree with the given key, see
+/// [`SavedEntitySpawner`].
+fn respawn_saved(world: &mut World, key: &SaveKey) -> Option<Entity> {
+    if key.0.starts_with("chunk:") {
+        return respawn_saved_tree(world, key);
+    }
+    let mut queue = CommandQueue::default();
+    let entity = {
+        let game_assets = world.get_resource::<GameAssets>()?;
+        let level = world.resource::<Assets<Level>>().get(&game_assets.level)?;
+        let asset_server = world.resource::<AssetServer>();
+        let mut commands = Commands::new(&mut queue, world);
+        if let Some(id) = key.0.strip_prefix("prop:") {
+            let prop = level.props.iter().find(|prop| prop.id == id)?;
+            spawn_prop(&mut commands, prop, asset_server)
+        } else {
+            let (region_id, index) = key.0.strip_prefix("scatter:")?.rsplit_once(':')?;
+            let index = index.parse().ok()?;
+            let region = level.scatter.iter().find(|region| region.id == region_id)?;
+            let transform = *level.scatter_placements(region).get(index)?;
+            spawn_scatter_tree(&mut commands, region, index, transform, game_assets)
+        }
+    };
+    queue.apply(world);
+    Some(entity)
+}
@@ -7,10 +7,52 @@
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 
-#[derive(Resource)]
-pub struct GameAssets {
-    pub player: Handle<Scene>,
-    pub tree: Handle<Scene>,
+mod actions;
+mod animation;
+mod auto_collider;
+mod camera;
+mod day_night;
+mod game_assets;
+mod interaction;
+mod level;
+mod menu;
+mod movement;
+mod ron_asset;
+mod save;
+mod scatter;
+mod scene_import;
+mod settings;
+mod storage;
+mod streaming;
+mod terrain;
+
+use actions::ActionPlugin;
+use animation::{LocomotionAnimator, PlayerAnimationPlugin};
+use auto_collider::AutoColliderPlugin;
+use camera::{apply_camera_position, camera_movement};
+use day_night::DayNightPlugin;
+use game_assets::{GameAssets, GameAssetsPlugin};
+use interaction::InteractionPlugin;
+use level::LevelPlugin;
+use menu::MenuPlugin;
+use movement::{Grounded, MovementPlugin, Stance};
+use save::SavePlugin;
+use scatter::ScatterPlugin;
+use scene_import::SceneImportPlugin;
+use settings::SettingsPlugin;
+use streaming::StreamingPlugin;
+use terrain::TerrainPlugin;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum AppState {
+    /// Waiting for everything in the asset manifest.
+    Loading,
+    MainMenu,
+    /// The level is spawned and gameplay systems run.
+    InGame,
+    /// Pushed on top of [`AppState::InGame`], which keeps the level around
+    /// but stops its systems.
+    Paused,
 }
 
 #[derive(Component, Reflect)]
@@ -24,170 +66,68 @@ pub struct PlayerBundle {
     player: Player,
     velocity: Velocity,
     character_controller: KinematicCharacterController,
-    locked_axes: LockedAxes,
+    grounded: Grounded,
+    stance: Stance,
+    animator: LocomotionAnimator,
 }
 impl PlayerBundle {
-    pub fn new(assets: &GameAssets) -> Self {
+    pub fn new(assets: &GameAssets, transform: Transform) -> Self {
         PlayerBundle {
             scene_bundle: SceneBundle {
                 scene: assets.player.clone(),
+                transform,
                 ..Default::default()
             },
-            rigid_body: RigidBody::Dynamic,
+            rigid_body: RigidBody::KinematicPositionBased,
             collider: Collider::capsule_y(1.0, 0.5),
             player: Player,
             velocity: Velocity::default(),
-            character_controller: KinematicCharacterController::default(),
-            locked_axes: LockedAxes::ROTATION_LOCKED,
-        }
-    }
-}
-
-#[derive(Component, Reflect)]
-pub struct CameraController {
-    pub rotation_y: f32,
-    pub rotation_x: f32,
-    pub distance: f32,
-    pub lock_entity: Entity,
-}
-impl CameraController {
-    pub fn new(lock_entity: Entity) -> Self {
-        CameraController {
-            rotation_y: 0.0,
-            rotation_x: 0.0,
-            distance: 5.0,
-            lock_entity,
+            character_controller: KinematicCharacterController {
+                up: Vec3::Y,
+                offset: CharacterLength::Absolute(0.01),
+                slide: true,
+                max_slope_climb_angle: 45.0f32.to_radians(),
+                min_slope_slide_angle: 30.0f32.to_radians(),
+                autostep: Some(CharacterAutostep {
+                    max_height: CharacterLength::Absolute(0.4),
+                    min_width: CharacterLength::Absolute(0.2),
+                    include_dynamic_bodies: true,
+                }),
+                snap_to_ground: Some(CharacterLength::Absolute(0.3)),
+                ..Default::default()
+            },
+            grounded: Grounded::default(),
+            stance: Stance::default(),
+            animator: LocomotionAnimator::default(),
         }
     }
 }
 
 fn main() {
     App::new()
-        .insert_resource(AmbientLight {
-            color: Color::WHITE,
-            brightness: 1.0 / 5.0f32,
-        })
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
-            window: WindowDescriptor {
-                cursor_visible: false,
-                cursor_grab_mode: bevy::window::CursorGrabMode::Locked,
-                ..Default::default()
-            },
-            ..Default::default()
-        }))
+        .add_plugins(DefaultPlugins)
         .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
         .add_plugin(RapierDebugRenderPlugin::default())
-        .add_startup_system(setup)
-        .add_system(camera_movement)
-        .add_system(keyboard_input)
-        .add_system(apply_camera_position)
-        .add_system(bevy::window::close_on_esc)
+        .add_state(AppState::Loading)
+        .add_plugin(SettingsPlugin)
+        .add_plugin(ActionPlugin)
+        .add_plugin(MovementPlugin)
+        .add_plugin(GameAssetsPlugin)
+        .add_plugin(LevelPlugin)
+        .add_plugin(MenuPlugin)
+        .add_plugin(SavePlugin)
+        .add_plugin(StreamingPlugin)
+        .add_plugin(PlayerAnimationPlugin)
+        .add_plugin(DayNightPlugin)
+        .add_plugin(InteractionPlugin)
+        .add_plugin(ScatterPlugin)
+        .add_plugin(AutoColliderPlugin)
+        .add_plugin(TerrainPlugin)
+        .add_plugin(SceneImportPlugin)
+        .add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .with_system(camera_movement)
+                .with_system(apply_camera_position),
+        )
         .run();
 }
-
-fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let assets = GameAssets {
-        player: asset_server.load("human.glb#Scene0"),
-        tree: asset_server.load("tree.glb#Scene0"),
-    };
-    let player = commands.spawn(PlayerBundle::new(&assets)).id();
-    commands.spawn((
-        Camera3dBundle {
-            transform: Transform::from_xyz(5.0, 5.0, 5.0).looking_at(Vec3::ZERO, Vec3::Y),
-            ..Default::default()
-        },
-        CameraController::new(player),
-    ));
-
-    commands.spawn((
-        RigidBody::Fixed,
-        Collider::cuboid(1000.0, 0.1, 1000.0),
-        Transform::from_xyz(0.0, -2.0, 0.0),
-        GlobalTransform::default(),
-    ));
-
-    commands.spawn(SceneBundle {
-        scene: asset_server.load("tree.glb#Scene0"),
-        transform: Transform::from_xyz(2.0, 0.0, 0.0),
-        ..Default::default()
-    });
-}
-
-pub fn camera_movement(
-    mut mouse_motion_events: EventReader<bevy::input::mouse::MouseMotion>,
-    mut scroll_events: EventReader<bevy::input::mouse::MouseWheel>,
-    mut camera_controller_query: Query<&mut CameraController>,
-) {
-    if let Ok(mut camera_controller) = camera_controller_query.get_single_mut() {
-        for mouse_event in mouse_motion_events.iter() {
-            camera_controller.rotation_x += (mouse_event.delta.y as f32) * 0.01;
-            camera_controller.rotation_x = camera_controller
-                .rotation_x
-                .min(std::f32::consts::PI / 2.0 * 0.9)
-                .max(-std::f32::consts::PI / 2.0 * 0.9);
-            camera_controller.rotation_y -= (mouse_event.delta.x as f32) * 0.01;
-        }
-        for scroll_event in scroll_events.iter() {
-            camera_controller.distance += (scroll_event.y as f32) * 0.01;
-            camera_controller.distance = camera_controller.distance.min(10.0).max(2.0);
-        }
-    }
-}
-
-pub fn apply_camera_position(
-    mut camera_query: Query<(&mut Transform, &CameraController)>,
-    entity_position_query: Query<&Transform, Without<CameraController>>,
-) {
-    if let Ok((mut camera_transform, camera_controller)) = camera_query.get_single_mut() {
-        if let Ok(look_at_transform) = entity_position_query.get(camera_controller.lock_entity) {
-            let distance = camera_controller.distance;
-            let rot_y = camera_controller.rotation_y;
-            let rot_x = camera_controller.rotation_x;
-            *camera_transform = Transform::from_xyz(
-                look_at_transform.translation.x + distance * (rot_y.sin() * rot_x.cos()),
-                look_at_transform.translation.y + distance * rot_x.sin(),
-                look_at_transform.translation.z + distance * (rot_y.cos() * rot_x.cos()),
-            )
-            .looking_at(look_at_transform.translation, Vec3::Y);
-        }
-    }
-}
-
-pub fn keyboard_input(
-    keys: Res<Input<KeyCode>>,
-    camera_query: Query<&CameraController>,
-    mut entity_position_query: Query<(&mut Transform, &mut Velocity), Without<CameraController>>,
-) {
-    if keys.pressed(KeyCode::W) {
-        if let Ok(camera_controller) = camera_query.get_single() {
-            if let Ok((mut look_at_transform, mut velocity)) =
-                entity_position_query.get_mut(camera_controller.lock_entity)
-            {
-                let vector = Vec3::new(
-                    camera_controller.rotation_y.sin(),
-                    0.0,
-                    camera_controller.rotation_y.cos(),
-                );
-                let direction = look_at_transform.translation + vector;
-                look_at_transform.look_at(direction, Vec3::Y);
-                velocity.linvel = -vector;
-            }
-        }
-    }
-    if keys.pressed(KeyCode::S) {
-        if let Ok(camera_controller) = camera_query.get_single() {
-            if let Ok((mut look_at_transform, mut velocity)) =
-                entity_position_query.get_mut(camera_controller.lock_entity)
-            {
-                let vector = Vec3::new(
-                    camera_controller.rotation_y.sin(),
-                    0.0,
-                    camera_controller.rotation_y.cos(),
-                );
-                let direction = look_at_transform.translation - vector;
-                look_at_transform.look_at(direction, Vec3::Y);
-                velocity.linvel = vector;
-            }
-        }
-    }
-}
@@ -0,0 +1,503 @@
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy::window::CursorGrabMode;
+use bevy_rapier3d::prelude::*;
+
+use crate::actions::{Action, ActionState};
+use crate::camera::CameraSettings;
+use crate::game_assets::GameAssets;
+use crate::save::{self, PendingLoad, AUTOSAVE_SLOT};
+use crate::settings::{AudioSettings, GraphicsSettings};
+use crate::AppState;
+
+const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
+const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
+const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.55, 0.35);
+
+/// Marks the root of the main menu, including its camera.
+#[derive(Component)]
+struct MainMenu;
+
+/// Marks the root of the pause overlay.
+#[derive(Component)]
+struct PauseMenu;
+
+/// Pages of the pause overlay, only one of them is displayed at a time.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+enum PausePage {
+    Main,
+    Settings,
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+enum MenuButton {
+    Play,
+    /// Starts the game from the autosave.
+    Continue,
+    Quit,
+    Resume,
+    OpenPage(PausePage),
+    QuitToMenu,
+    /// Steps a setting up or down, toggles flip in both directions.
+    Adjust(Setting, f32),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Setting {
+    MouseSensitivity,
+    InvertY,
+    Volume,
+    Fullscreen,
+    VSync,
+    Msaa,
+    Brightness,
+}
+impl Setting {
+    const ALL: [Setting; 7] = [
+        Setting::MouseSensitivity,
+        Setting::InvertY,
+        Setting::Volume,
+        Setting::Fullscreen,
+        Setting::VSync,
+        Setting::Msaa,
+        Setting::Brightness,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Setting::MouseSensitivity => "Mouse sensitivity",
+            Setting::InvertY => "Invert Y",
+            Setting::Volume => "Volume",
+            Setting::Fullscreen => "Fullscreen",
+            Setting::VSync => "VSync",
+            Setting::Msaa => "Anti-aliasing",
+            Setting::Brightness => "Brightness",
+        }
+    }
+
+    fn value(
+        self,
+        camera: &CameraSettings,
+        audio: &AudioSettings,
+        graphics: &GraphicsSettings,
+    ) -> String {
+        let on_off = |value: bool| String::from(if value { "On" } else { "Off" });
+        match self {
+            Setting::MouseSensitivity => format!("{:.4}", camera.mouse_sensitivity),
+            Setting::InvertY => on_off(camera.invert_y),
+            Setting::Volume => format!("{:.0}%", audio.volume * 100.0),
+            Setting::Fullscreen => on_off(graphics.fullscreen),
+            Setting::VSync => on_off(graphics.vsync),
+            Setting::Msaa => on_off(graphics.msaa),
+            Setting::Brightness => format!("{:.2}", graphics.ambient_brightness),
+        }
+    }
+
+    fn adjust(
+        self,
+        step: f32,
+        camera: &mut CameraSettings,
+        audio: &mut AudioSettings,
+        graphics: &mut GraphicsSettings,
+    ) {
+        match self {
+            Setting::MouseSensitivity => {
+                camera.mouse_sensitivity =
+                    (camera.mouse_sensitivity * 1.25f32.powf(step)).clamp(0.001, 0.1);
+            }
+            Setting::InvertY => camera.invert_y = !camera.invert_y,
+            Setting::Volume => audio.volume = (audio.volume + step * 0.1).clamp(0.0, 1.0),
+            Setting::Fullscreen => graphics.fullscreen = !graphics.fullscreen,
+            Setting::VSync => graphics.vsync = !graphics.vsync,
+            Setting::Msaa => graphics.msaa = !graphics.msaa,
+            Setting::Brightness => {
+                graphics.ambient_brightness =
+                    (graphics.ambient_brightness + step * 0.05).clamp(0.0, 1.0);
+            }
+        }
+    }
+}
+
+/// Text showing the current value of a setting.
+#[derive(Component)]
+struct SettingValue(Setting);
+
+pub struct MenuPlugin;
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(toggle_pause)
+            .add_system(button_colors)
+            .add_system(menu_buttons)
+            .add_system_set(
+                SystemSet::on_enter(AppState::MainMenu)
+                    .with_system(spawn_main_menu)
+                    .with_system(release_cursor),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::MainMenu).with_system(despawn_main_menu))
+            .add_system_set(
+                SystemSet::on_enter(AppState::Paused)
+                    .with_system(spawn_pause_menu)
+                    .with_system(release_cursor)
+                    .with_system(stop_physics),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Paused).with_system(update_setting_values),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Paused)
+                    .with_system(despawn_pause_menu)
+                    .with_system(resume_physics),
+            )
+            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(grab_cursor))
+            .add_system_set(SystemSet::on_resume(AppState::InGame).with_system(grab_cursor));
+    }
+}
+
+/// Pauses and resumes the game. Pausing pushes [`AppState::Paused`] on top
+/// of [`AppState::InGame`], so the level stays spawned.
+fn toggle_pause(actions: Res<ActionState>, mut app_state: ResMut<State<AppState>>) {
+    if !actions.just_pressed(Action::Pause) {
+        return;
+    }
+    // Fails if another transition is already queued this frame, which then
+    // takes precedence.
+    let _ = match app_state.current() {
+        AppState::InGame => app_state.push(AppState::Paused),
+        AppState::Paused => app_state.pop(),
+        AppState::Loading | AppState::MainMenu => Ok(()),
+    };
+}
+
+fn set_cursor_grab(windows: &mut Windows, grab: bool) {
+    if let Some(window) = windows.get_primary_mut() {
+        if grab {
+            window.set_cursor_grab_mode(CursorGrabMode::Locked);
+        } else {
+            window.set_cursor_grab_mode(CursorGrabMode::None);
+        }
+        window.set_cursor_visibility(!grab);
+    }
+}
+
+fn grab_cursor(mut windows: ResMut<Windows>) {
+    set_cursor_grab(&mut windows, true);
+}
+
+fn release_cursor(mut windows: ResMut<Windows>) {
+    set_cursor_grab(&mut windows, false);
+}
+
+fn stop_physics(mut rapier_configuration: ResMut<RapierConfiguration>) {
+    rapier_configuration.physics_pipeline_active = false;
+}
+
+fn resume_physics(mut rapier_configuration: ResMut<RapierConfiguration>) {
+    rapier_configuration.physics_pipeline_active = true;
+}
+
+fn button_colors(
+    mut button_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interaction, mut color) in button_query.iter_mut() {
+        *color = match interaction {
+            Interaction::Clicked => PRESSED_BUTTON,
+            Interaction::Hovered => HOVERED_BUTTON,
+            Interaction::None => NORMAL_BUTTON,
+        }
+        .into();
+    }
+}
+
+/// Spawns a button with a centered label as child of `parent`.
+fn spawn_button(
+    parent: &mut ChildBuilder,
+    font: &Handle<Font>,
+    label: &str,
+    width: f32,
+    button: MenuButton,
+) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    size: Size::new(Val::Px(width), Val::Px(56.0)),
+                    margin: UiRect::all(Val::Px(8.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                background_color: NORMAL_BUTTON.into(),
+                ..Default::default()
+            },
+            button,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                label,
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 32.0,
+                    color: Color::WHITE,
+                },
+            ));
+        });
+}
+
+fn spawn_main_menu(mut commands: Commands, game_assets: Res<GameAssets>) {
+    // The level camera only exists in game, the menu needs its own.
+    commands.spawn((Camera2dBundle::default(), MainMenu));
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                background_color: Color::rgb(0.05, 0.08, 0.05).into(),
+                ..Default::default()
+            },
+            MainMenu,
+        ))
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(
+                    "Forest",
+                    TextStyle {
+                        font: game_assets.font.clone(),
+                        font_size: 72.0,
+                        color: Color::WHITE,
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::bottom(Val::Px(32.0)),
+                    ..Default::default()
+                }),
+            );
+            if save::slot_exists(AUTOSAVE_SLOT) {
+                spawn_button(
+                    parent,
+                    &game_assets.font,
+                    "Continue",
+                    240.0,
+                    MenuButton::Continue,
+                );
+            }
+            spawn_button(parent, &game_assets.font, "Play", 240.0, MenuButton::Play);
+            spawn_button(parent, &game_assets.font, "Quit", 240.0, MenuButton::Quit);
+        });
+}
+
+fn despawn_main_menu(mut commands: Commands, menu_query: Query<Entity, With<MainMenu>>) {
+    for entity in menu_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn spawn_pause_menu(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    camera_settings: Res<CameraSettings>,
+    audio_settings: Res<AudioSettings>,
+    graphics_settings: Res<GraphicsSettings>,
+) {
+    let font = &game_assets.font;
+    let text_style = TextStyle {
+        font: font.clone(),
+        font_size: 32.0,
+        color: Color::WHITE,
+    };
+    let page_style = |page: PausePage| Style {
+        display: if page == PausePage::Main {
+            Display::Flex
+        } else {
+            Display::None
+        },
+        flex_direction: FlexDirection::Column,
+        align_items: AlignItems::Center,
+        ..Default::default()
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                ..Default::default()
+            },
+            PauseMenu,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: page_style(PausePage::Main),
+                        ..Default::default()
+                    },
+                    PausePage::Main,
+                ))
+                .with_children(|parent| {
+                    spawn_button(parent, font, "Resume", 240.0, MenuButton::Resume);
+                    spawn_button(
+                        parent,
+                        font,
+                        "Settings",
+                        240.0,
+                        MenuButton::OpenPage(PausePage::Settings),
+                    );
+                    spawn_button(parent, font, "Quit", 240.0, MenuButton::QuitToMenu);
+                });
+
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: page_style(PausePage::Settings),
+                        ..Default::default()
+                    },
+                    PausePage::Settings,
+                ))
+                .with_children(|parent| {
+                    for setting in Setting::ALL {
+                        parent
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    align_items: AlignItems::Center,
+                                    ..Default::default()
+                                },
+                                ..Default::default()
+                            })
+                            .with_children(|parent| {
+                                parent.spawn(
+                                    TextBundle::from_section(setting.label(), text_style.clone())
+                                        .with_style(Style {
+                                            size: Size::new(Val::Px(300.0), Val::Auto),
+                                            ..Default::default()
+                                        }),
+                                );
+                                spawn_button(
+                                    parent,
+                                    font,
+                                    "-",
+                                    56.0,
+                                    MenuButton::Adjust(setting, -1.0),
+                                );
+                                parent.spawn((
+                                    TextBundle::from_section(
+                                        setting.value(
+                                            &camera_settings,
+                                            &audio_settings,
+                                            &graphics_settings,
+                                        ),
+                                        text_style.clone(),
+                                    )
+                                    .with_style(Style {
+                                        size: Size::new(Val::Px(120.0), Val::Auto),
+                                        margin: UiRect::horizontal(Val::Px(16.0)),
+                                        ..Default::default()
+                                    }),
+                                    SettingValue(setting),
+                                ));
+                                spawn_button(
+                                    parent,
+                                    font,
+                                    "+",
+                                    56.0,
+                                    MenuButton::Adjust(setting, 1.0),
+                                );
+                            });
+                    }
+                    spawn_button(
+                        parent,
+                        font,
+                        "Back",
+                        240.0,
+                        MenuButton::OpenPage(PausePage::Main),
+                    );
+                });
+        });
+}
+
+fn despawn_pause_menu(mut commands: Commands, menu_query: Query<Entity, With<PauseMenu>>) {
+    for entity in menu_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn update_setting_values(
+    camera_settings: Res<CameraSettings>,
+    audio_settings: Res<AudioSettings>,
+    graphics_settings: Res<GraphicsSettings>,
+    mut value_query: Query<(&mut Text, &SettingValue)>,
+) {
+    if !(camera_settings.is_changed()
+        || audio_settings.is_changed()
+        || graphics_settings.is_changed())
+    {
+        return;
+    }
+    for (mut text, setting_value) in value_query.iter_mut() {
+        text.sections[0].value =
+            setting_value
+                .0
+                .value(&camera_settings, &audio_settings, &graphics_settings);
+    }
+}
+
+fn menu_buttons(
+    button_query: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    mut page_query: Query<(&mut Style, &PausePage)>,
+    mut app_state: ResMut<State<AppState>>,
+    mut app_exit_events: EventWriter<AppExit>,
+    mut pending_load: ResMut<PendingLoad>,
+    mut camera_settings: ResMut<CameraSettings>,
+    mut audio_settings: ResMut<AudioSettings>,
+    mut graphics_settings: ResMut<GraphicsSettings>,
+) {
+    for (interaction, button) in button_query.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        match *button {
+            MenuButton::Play => {
+                let _ = app_state.set(AppState::InGame);
+            }
+            MenuButton::Continue => {
+                if app_state.set(AppState::InGame).is_ok() {
+                    pending_load.0 = Some(AUTOSAVE_SLOT);
+                }
+            }
+            MenuButton::Quit => app_exit_events.send(AppExit),
+            MenuButton::Resume => {
+                let _ = app_state.pop();
+            }
+            MenuButton::OpenPage(open_page) => {
+                for (mut style, page) in page_query.iter_mut() {
+                    style.display = if *page == open_page {
+                        Display::Flex
+                    } else {
+                        Display::None
+                    };
+                }
+            }
+            MenuButton::QuitToMenu => {
+                let _ = app_state.replace(AppState::MainMenu);
+            }
+            MenuButton::Adjust(setting, step) => setting.adjust(
+                step,
+                &mut camera_settings,
+                &mut audio_settings,
+                &mut graphics_settings,
+            ),
+        }
+    }
+}
@@ -0,0 +1,344 @@
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy_rapier3d::prelude::*;
+use serde::Deserialize;
+
+use crate::actions::{Action, ActionState};
+use crate::camera::{CameraController, CameraMode};
+use crate::ron_asset::RonResourcePlugin;
+use crate::AppState;
+
+/// Tracks whether the entity stands on the ground, together with the timers
+/// used for coyote time and jump buffering.
+#[derive(Component, Reflect, Default)]
+pub struct Grounded {
+    pub grounded: bool,
+    /// Remaining time in which a jump is still allowed after leaving the ground.
+    pub coyote_timer: f32,
+    /// Remaining time in which a pressed jump is still executed on landing.
+    pub jump_buffer_timer: f32,
+}
+
+#[derive(Component, Reflect, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[reflect(Component)]
+pub enum Stance {
+    #[default]
+    Walk,
+    Sprint,
+    Crouch,
+}
+
+/// Piecewise linear curve given as `(x, y)` points sorted by `x`.
+///
+/// Values outside of the first and last point are clamped.
+#[derive(Deserialize, Clone, Debug)]
+pub struct ResponseCurve(pub Vec<(f32, f32)>);
+impl ResponseCurve {
+    pub fn constant(value: f32) -> Self {
+        ResponseCurve(vec![(0.0, value)])
+    }
+
+    pub fn sample(&self, x: f32) -> f32 {
+        let points = &self.0;
+        match (points.first(), points.last()) {
+            (Some(first), _) if x <= first.0 => first.1,
+            (_, Some(last)) if x >= last.0 => last.1,
+            (None, _) | (_, None) => 1.0,
+            _ => {
+                let index = points.iter().position(|point| point.0 > x).unwrap_or(0);
+                let (x0, y0) = points[index - 1];
+                let (x1, y1) = points[index];
+                y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+            }
+        }
+    }
+}
+
+/// Tuning values for the player movement, loaded from
+/// `assets/player.movement.ron`.
+///
+/// The acceleration and deceleration curves are sampled with the current
+/// speed relative to the target speed and scale `acceleration` and
+/// `deceleration` respectively.
+#[derive(Resource, Deserialize, TypeUuid, Clone, Debug)]
+#[uuid = "3bbe3ced-3a72-4d27-9e4e-05f464a94b3d"]
+#[serde(default)]
+pub struct MovementSettings {
+    pub walk_speed: f32,
+    pub sprint_speed: f32,
+    pub crouch_speed: f32,
+    pub acceleration: f32,
+    pub acceleration_curve: ResponseCurve,
+    pub deceleration: f32,
+    pub deceleration_curve: ResponseCurve,
+    pub gravity: f32,
+    pub jump_speed: f32,
+    pub coyote_time: f32,
+    pub jump_buffer_time: f32,
+    pub capsule_radius: f32,
+    pub standing_half_height: f32,
+    pub crouching_half_height: f32,
+}
+impl Default for MovementSettings {
+    fn default() -> Self {
+        MovementSettings {
+            walk_speed: 3.0,
+            sprint_speed: 6.0,
+            crouch_speed: 1.5,
+            acceleration: 30.0,
+            acceleration_curve: ResponseCurve::constant(1.0),
+            deceleration: 40.0,
+            deceleration_curve: ResponseCurve::constant(1.0),
+            gravity: 20.0,
+            jump_speed: 8.0,
+            coyote_time: 0.15,
+            jump_buffer_time: 0.15,
+            capsule_radius: 0.5,
+            standing_half_height: 1.0,
+            crouching_half_height: 0.5,
+        }
+    }
+}
+impl MovementSettings {
+    pub fn speed(&self, stance: Stance) -> f32 {
+        match stance {
+            Stance::Walk => self.walk_speed,
+            Stance::Sprint => self.sprint_speed,
+            Stance::Crouch => self.crouch_speed,
+        }
+    }
+
+    pub fn half_height(&self, stance: Stance) -> f32 {
+        match stance {
+            Stance::Crouch => self.crouching_half_height,
+            Stance::Walk | Stance::Sprint => self.standing_half_height,
+        }
+    }
+
+    /// Capsule of the stance, which starts at the bottom of the standing
+    /// capsule around the entity's origin. Crouching lowers only its top, so
+    /// the entity and its model stay where they are.
+    pub fn collider(&self, stance: Stance) -> Collider {
+        let bottom = -self.standing_half_height;
+        let top = bottom + 2.0 * self.half_height(stance);
+        Collider::capsule(Vec3::Y * bottom, Vec3::Y * top, self.capsule_radius)
+    }
+
+    /// Moves the horizontal `velocity` towards `target` for one frame.
+    pub fn approach(
+        &self,
+        velocity: Vec2,
+        target: Vec2,
+        max_speed: f32,
+        delta_seconds: f32,
+    ) -> Vec2 {
+        let relative_speed = if max_speed > 0.0 {
+            velocity.length() / max_speed
+        } else {
+            1.0
+        };
+        let rate = if target == Vec2::ZERO {
+            self.deceleration * self.deceleration_curve.sample(relative_speed)
+        } else {
+            self.acceleration * self.acceleration_curve.sample(relative_speed)
+        };
+        let difference = target - velocity;
+        let max_step = rate * delta_seconds;
+        if difference.length() <= max_step {
+            target
+        } else {
+            velocity + difference.normalize() * max_step
+        }
+    }
+}
+
+pub struct MovementPlugin;
+impl Plugin for MovementPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(RonResourcePlugin::<MovementSettings>::new(
+            "player.movement.ron",
+            &["movement.ron"],
+        ))
+        .register_type::<Grounded>()
+        .register_type::<Stance>()
+        .add_system(apply_stance_collider)
+        .add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .with_system(update_grounded.before(keyboard_input))
+                .with_system(update_stance.before(keyboard_input))
+                .with_system(keyboard_input),
+        );
+    }
+}
+
+fn update_grounded(
+    time: Res<Time>,
+    movement_settings: Res<MovementSettings>,
+    mut grounded_query: Query<(&mut Grounded, Option<&KinematicCharacterControllerOutput>)>,
+) {
+    for (mut grounded, output) in grounded_query.iter_mut() {
+        grounded.grounded = output.map(|output| output.grounded).unwrap_or(false);
+        if grounded.grounded {
+            grounded.coyote_timer = movement_settings.coyote_time;
+        } else {
+            grounded.coyote_timer = (grounded.coyote_timer - time.delta_seconds()).max(0.0);
+        }
+    }
+}
+
+/// Switches between walking, sprinting and crouching and resizes the
+/// collider to match.
+///
+/// Standing up needs enough headroom, otherwise the entity stays crouched.
+/// Like the other movement, this stops while the free fly camera uses the
+/// actions.
+fn update_stance(
+    actions: Res<ActionState>,
+    movement_settings: Res<MovementSettings>,
+    rapier_context: Res<RapierContext>,
+    camera_query: Query<&CameraController>,
+    mut stance_query: Query<(Entity, &mut Stance, &mut Collider, &Transform)>,
+) {
+    if camera_query
+        .iter()
+        .any(|camera_controller| camera_controller.mode == CameraMode::FreeFly)
+    {
+        return;
+    }
+    let new_stance = if actions.pressed(Action::Crouch) {
+        Stance::Crouch
+    } else if actions.pressed(Action::Sprint) {
+        Stance::Sprint
+    } else {
+        Stance::Walk
+    };
+    for (entity, mut stance, mut collider, transform) in stance_query.iter_mut() {
+        if *stance == new_stance {
+            continue;
+        }
+        let old_half_height = movement_settings.half_height(*stance);
+        let new_half_height = movement_settings.half_height(new_stance);
+        if new_half_height > old_half_height {
+            // The bottom stays in place, so the top of the capsule rises by
+            // twice the difference.
+            let blocked = rapier_context
+                .cast_shape(
+                    transform.translation,
+                    transform.rotation,
+                    Vec3::Y,
+                    &movement_settings.collider(*stance),
+                    2.0 * (new_half_height - old_half_height),
+                    QueryFilter::default().exclude_collider(entity),
+                )
+                .is_some();
+            if blocked {
+                continue;
+            }
+        }
+        if old_half_height != new_half_height {
+            *collider = movement_settings.collider(new_stance);
+        }
+        *stance = new_stance;
+    }
+}
+
+/// Moves the entity the camera is locked to from the movement actions, with
+/// acceleration, gravity and jumping.
+pub fn keyboard_input(
+    actions: Res<ActionState>,
+    time: Res<Time>,
+    movement_settings: Res<MovementSettings>,
+    camera_query: Query<&CameraController>,
+    mut entity_position_query: Query<
+        (
+            &mut Transform,
+            &mut Velocity,
+            &mut KinematicCharacterController,
+            &mut Grounded,
+            &Stance,
+        ),
+        Without<CameraController>,
+    >,
+) {
+    if let Ok(camera_controller) = camera_query.get_single() {
+        if let Ok((
+            mut look_at_transform,
+            mut velocity,
+            mut character_controller,
+            mut grounded,
+            stance,
+        )) = entity_position_query.get_mut(camera_controller.lock_entity)
+        {
+            let forward = -Vec3::new(
+                camera_controller.rotation_y.sin(),
+                0.0,
+                camera_controller.rotation_y.cos(),
+            );
+            let right = Vec3::new(
+                camera_controller.rotation_y.cos(),
+                0.0,
+                -camera_controller.rotation_y.sin(),
+            );
+
+            // The free fly camera uses the movement actions itself.
+            let controls_player = camera_controller.mode != CameraMode::FreeFly;
+
+            // Diagonal input must not be faster than straight input.
+            let vector = if controls_player {
+                (forward * actions.value(Action::MoveForward)
+                    + right * actions.value(Action::Strafe))
+                .clamp_length_max(1.0)
+            } else {
+                Vec3::ZERO
+            };
+
+            let delta_seconds = time.delta_seconds();
+            let speed = movement_settings.speed(*stance);
+            let horizontal = movement_settings.approach(
+                Vec2::new(velocity.linvel.x, velocity.linvel.z),
+                Vec2::new(vector.x, vector.z) * speed,
+                speed,
+                delta_seconds,
+            );
+
+            if controls_player && actions.just_pressed(Action::Jump) {
+                grounded.jump_buffer_timer = movement_settings.jump_buffer_time;
+            } else {
+                grounded.jump_buffer_timer = (grounded.jump_buffer_timer - delta_seconds).max(0.0);
+            }
+            let mut vertical_speed = velocity.linvel.y;
+            if grounded.grounded {
+                vertical_speed = vertical_speed.max(0.0);
+            }
+            if grounded.jump_buffer_timer > 0.0 && grounded.coyote_timer > 0.0 {
+                vertical_speed = movement_settings.jump_speed;
+                grounded.jump_buffer_timer = 0.0;
+                grounded.coyote_timer = 0.0;
+            } else {
+                vertical_speed -= movement_settings.gravity * delta_seconds;
+            }
+
+            velocity.linvel = Vec3::new(horizontal.x, vertical_speed, horizontal.y);
+            // The body is kinematic, so movement only happens through the
+            // character controller which resolves slopes, steps and ground
+            // snapping for us.
+            character_controller.translation = Some(velocity.linvel * delta_seconds);
+
+            if vector != Vec3::ZERO {
+                let direction = look_at_transform.translation - vector;
+                look_at_transform.look_at(direction, Vec3::Y);
+            }
+        }
+    }
+}
+
+/// Sizes the collider for stances which were set without [`update_stance`],
+/// like when spawning or loading a save.
+fn apply_stance_collider(
+    movement_settings: Res<MovementSettings>,
+    mut stance_query: Query<(&Stance, &mut Collider), Changed<Stance>>,
+) {
+    for (stance, mut collider) in stance_query.iter_mut() {
+        *collider = movement_settings.collider(*stance);
+    }
+}
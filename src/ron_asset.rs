@@ -0,0 +1,121 @@
+use std::marker::PhantomData;
+
+use bevy::asset::{Asset, AssetLoader, BoxedFuture, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use serde::de::DeserializeOwned;
+
+/// Loads any deserializable asset type from a RON file.
+///
+/// Every asset type gets its own compound extension like `movement.ron`, so
+/// several of these loaders can be registered next to each other.
+pub struct RonAssetLoader<T> {
+    extensions: &'static [&'static str],
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> RonAssetLoader<T> {
+    pub fn new(extensions: &'static [&'static str]) -> Self {
+        RonAssetLoader {
+            extensions,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> AssetLoader for RonAssetLoader<T>
+where
+    T: DeserializeOwned + TypeUuid + Send + Sync + 'static,
+{
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let asset = ron::de::from_bytes::<T>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(asset));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        self.extensions
+    }
+}
+
+/// Loads a RON file into the resource `T` and replaces the resource again
+/// whenever the file is hot reloaded.
+///
+/// The resource holds its defaults until the file has loaded. It's updated
+/// in [`CoreStage::PreUpdate`], so the new values are seen by every system
+/// in the same frame.
+pub struct RonResourcePlugin<T> {
+    path: &'static str,
+    extensions: &'static [&'static str],
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> RonResourcePlugin<T> {
+    /// `extensions` are registered for the [`RonAssetLoader`] of `T`, `path`
+    /// has to end in one of them.
+    pub fn new(path: &'static str, extensions: &'static [&'static str]) -> Self {
+        RonResourcePlugin {
+            path,
+            extensions,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Plugin for RonResourcePlugin<T>
+where
+    T: Resource + Asset + FromWorld + Clone + DeserializeOwned,
+{
+    fn build(&self, app: &mut App) {
+        app.add_asset::<T>()
+            .add_asset_loader(RonAssetLoader::<T>::new(self.extensions))
+            .init_resource::<T>()
+            .insert_resource(RonResourceFile::<T> {
+                path: self.path,
+                handle: Handle::default(),
+            })
+            .add_startup_system(load_ron_resource::<T>)
+            .add_system_to_stage(CoreStage::PreUpdate, apply_ron_resource::<T>);
+    }
+}
+
+/// The file a [`RonResourcePlugin`] loads `T` from.
+#[derive(Resource)]
+pub struct RonResourceFile<T: Asset> {
+    path: &'static str,
+    handle: Handle<T>,
+}
+
+fn load_ron_resource<T: Asset>(
+    asset_server: Res<AssetServer>,
+    mut file: ResMut<RonResourceFile<T>>,
+) {
+    file.handle = asset_server.load(file.path);
+}
+
+/// Copies the loaded file into the resource, also after hot reloads.
+pub fn apply_ron_resource<T: Resource + Asset + Clone>(
+    mut events: EventReader<AssetEvent<T>>,
+    assets: Res<Assets<T>>,
+    file: Res<RonResourceFile<T>>,
+    mut resource: ResMut<T>,
+) {
+    for event in events.iter() {
+        match event {
+            AssetEvent::Created { handle: changed } | AssetEvent::Modified { handle: changed }
+                if *changed == file.handle =>
+            {
+                if let Some(loaded) = assets.get(changed) {
+                    *resource = loaded.clone();
+                }
+            }
+            _ => {}
+        }
+    }
+}
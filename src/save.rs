@@ -0,0 +1,329 @@
+use std::any::TypeId;
+
+use bevy::ecs::system::Command;
+use bevy::hierarchy::despawn_with_children_recursive;
+use bevy::prelude::*;
+use bevy::reflect::GetTypeRegistration;
+use bevy::scene::serde::SceneDeserializer;
+use bevy::scene::DynamicEntity;
+use bevy::utils::{HashMap, HashSet};
+use bevy_rapier3d::prelude::*;
+use serde::de::DeserializeSeed;
+use serde::{Deserialize, Serialize};
+
+use crate::actions::{Action, ActionState};
+use crate::camera::{CameraController, CameraMode};
+use crate::movement::Stance;
+use crate::storage;
+use crate::{AppState, Player};
+
+/// Migrations of the saved scene text, `MIGRATIONS[i]` upgrades a save of
+/// version `i + 1` to version `i + 2`.
+///
+/// Append a migration whenever a saved component changes incompatibly, like
+/// a renamed field or type.
+const MIGRATIONS: &[fn(&mut String)] = &[];
+const SAVE_VERSION: u32 = MIGRATIONS.len() as u32 + 1;
+
+/// Slot written periodically while playing, to resume after a crash.
+pub const AUTOSAVE_SLOT: u32 = 0;
+const QUICKSAVE_SLOT: u32 = 1;
+const AUTOSAVE_INTERVAL: f32 = 30.0;
+
+/// Identifies an entity across sessions, as entity ids are different every
+/// time the level is spawned.
+///
+/// Only entities with a key are saved. Keyed entities are removed with
+/// [`DespawnSaved`], so saves know they are gone.
+#[derive(Component, Reflect, FromReflect, Default, Clone, PartialEq, Eq, Hash, Debug)]
+#[reflect(Component)]
+pub struct SaveKey(pub String);
+
+/// Keys of the saved entities removed while playing, reset whenever the
+/// level is spawned.
+#[derive(Resource, Default)]
+pub struct RemovedSaveKeys(pub HashSet<SaveKey>);
+
+/// Spawns the entity with the given key again, for saves made before the
+/// entity was removed. Set by whatever spawns the keyed entities.
+#[derive(Resource)]
+pub struct SavedEntitySpawner(pub fn(&mut World, &SaveKey) -> Option<Entity>);
+
+/// Despawns the entity with its children and remembers its [`SaveKey`], so
+/// it stays removed when the game is saved and loaded.
+pub struct DespawnSaved(pub Entity);
+impl Command for DespawnSaved {
+    fn write(self, world: &mut World) {
+        if let Some(key) = world.get::<SaveKey>(self.0).cloned() {
+            world
+                .get_resource_or_insert_with(RemovedSaveKeys::default)
+                .0
+                .insert(key);
+        }
+        despawn_with_children_recursive(world, self.0);
+    }
+}
+
+/// Types of the components which are written to saves, see
+/// [`RegisterSaved::register_saved`].
+#[derive(Resource, Default)]
+struct SavedComponents(Vec<TypeId>);
+
+pub trait RegisterSaved {
+    /// Registers `T` for reflection and writes it to saves for every entity
+    /// with a [`SaveKey`].
+    fn register_saved<T: Component + GetTypeRegistration>(&mut self) -> &mut Self;
+}
+impl RegisterSaved for App {
+    fn register_saved<T: Component + GetTypeRegistration>(&mut self) -> &mut Self {
+        self.register_type::<T>();
+        self.world
+            .get_resource_or_insert_with(SavedComponents::default)
+            .0
+            .push(TypeId::of::<T>());
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SaveFile {
+    version: u32,
+    /// Saved entities as a reflected [`DynamicScene`] in RON.
+    scene: String,
+    /// Keys of the entities which were removed, see [`RemovedSaveKeys`].
+    #[serde(default)]
+    removed: Vec<String>,
+}
+
+fn slot_file_name(slot: u32) -> String {
+    format!("save{}.ron", slot)
+}
+
+pub fn slot_exists(slot: u32) -> bool {
+    storage::read(&slot_file_name(slot)).is_some()
+}
+
+/// Writes every entity with a [`SaveKey`] to the given slot.
+pub struct SaveGame {
+    pub slot: u32,
+}
+impl Command for SaveGame {
+    fn write(self, world: &mut World) {
+        let result = serialize_world(world).and_then(|save| {
+            let contents = ron::to_string(&save).map_err(|err| err.to_string())?;
+            storage::write(&slot_file_name(self.slot), &contents)
+        });
+        match result {
+            Ok(()) => info!("Saved to slot {}", self.slot),
+            Err(err) => error!("Could not save to slot {}: {}", self.slot, err),
+        }
+    }
+}
+
+/// Restores the entities of the spawned level from the given slot.
+pub struct LoadGame {
+    pub slot: u32,
+}
+impl Command for LoadGame {
+    fn write(self, world: &mut World) {
+        let Some(contents) = storage::read(&slot_file_name(self.slot)) else {
+            warn!("Save slot {} is empty", self.slot);
+            return;
+        };
+        let result = ron::from_str::<SaveFile>(&contents)
+            .map_err(|err| err.to_string())
+            .and_then(|mut save| {
+                migrate(&mut save)?;
+                apply_save(world, &save)
+            });
+        match result {
+            Ok(()) => info!("Loaded slot {}", self.slot),
+            Err(err) => error!("Could not load slot {}: {}", self.slot, err),
+        }
+    }
+}
+
+fn migrate(save: &mut SaveFile) -> Result<(), String> {
+    if save.version == 0 || save.version > SAVE_VERSION {
+        return Err(format!(
+            "unsupported save version {}, expected at most {}",
+            save.version, SAVE_VERSION
+        ));
+    }
+    for migration in &MIGRATIONS[save.version as usize - 1..] {
+        migration(&mut save.scene);
+    }
+    save.version = SAVE_VERSION;
+    Ok(())
+}
+
+fn serialize_world(world: &World) -> Result<SaveFile, String> {
+    let type_registry = world.resource::<AppTypeRegistry>();
+    let registry = type_registry.read();
+    let saved_components = world.resource::<SavedComponents>();
+
+    let mut scene = DynamicScene::default();
+    for entity in world.iter_entities() {
+        if world.get::<SaveKey>(entity).is_none() {
+            continue;
+        }
+        let components = saved_components
+            .0
+            .iter()
+            .filter_map(|type_id| {
+                let reflect_component = registry.get(*type_id)?.data::<ReflectComponent>()?;
+                Some(reflect_component.reflect(world, entity)?.clone_value())
+            })
+            .collect();
+        scene.entities.push(DynamicEntity {
+            entity: entity.index(),
+            components,
+        });
+    }
+    drop(registry);
+
+    let scene = scene
+        .serialize_ron(type_registry)
+        .map_err(|err| err.to_string())?;
+    let mut removed: Vec<String> = world
+        .get_resource::<RemovedSaveKeys>()
+        .map(|removed| removed.0.iter().map(|key| key.0.clone()).collect())
+        .unwrap_or_default();
+    removed.sort();
+    Ok(SaveFile {
+        version: SAVE_VERSION,
+        scene,
+        removed,
+    })
+}
+
+fn apply_save(world: &mut World, save: &SaveFile) -> Result<(), String> {
+    let type_registry = world.resource::<AppTypeRegistry>().clone();
+    let registry = type_registry.read();
+    let mut deserializer =
+        ron::Deserializer::from_str(&save.scene).map_err(|err| err.to_string())?;
+    let scene = SceneDeserializer {
+        type_registry: &registry,
+    }
+    .deserialize(&mut deserializer)
+    .map_err(|err| err.to_string())?;
+
+    let mut keyed_entities: HashMap<SaveKey, Entity> = HashMap::default();
+    for entity in world.iter_entities() {
+        if let Some(key) = world.get::<SaveKey>(entity) {
+            keyed_entities.insert(key.clone(), entity);
+        }
+    }
+
+    for saved_entity in scene.entities.iter() {
+        let key = saved_entity.components.iter().find_map(|component| {
+            if component.type_name() == std::any::type_name::<SaveKey>() {
+                SaveKey::from_reflect(component.as_ref())
+            } else {
+                None
+            }
+        });
+        let Some(key) = key else {
+            continue;
+        };
+        // Entities removed after the save was made come back.
+        let entity = keyed_entities.get(&key).copied().or_else(|| {
+            let spawn = world.get_resource::<SavedEntitySpawner>()?.0;
+            spawn(world, &key)
+        });
+        // Also happens to streamed entities whose chunk isn't loaded, which
+        // is common enough that it isn't worth a warning.
+        let Some(entity) = entity else {
+            debug!("Skipping saved entity {:?}, which doesn't exist", key.0);
+            continue;
+        };
+        for component in saved_entity.components.iter() {
+            let Some(reflect_component) = registry
+                .get_with_name(component.type_name())
+                .and_then(|registration| registration.data::<ReflectComponent>())
+            else {
+                warn!("Skipping unregistered component {}", component.type_name());
+                continue;
+            };
+            reflect_component.apply_or_insert(world, entity, component.as_ref());
+        }
+    }
+
+    // Entities which aren't in the save at all were added to the level
+    // later, only the removed ones go away.
+    let removed: HashSet<SaveKey> = save.removed.iter().cloned().map(SaveKey).collect();
+    for key in removed.iter() {
+        if let Some(entity) = keyed_entities.get(key) {
+            despawn_with_children_recursive(world, *entity);
+        }
+    }
+    world.insert_resource(RemovedSaveKeys(removed));
+    Ok(())
+}
+
+/// Slot which is loaded as soon as the level is spawned, like when continuing
+/// from the main menu.
+#[derive(Resource, Default)]
+pub struct PendingLoad(pub Option<u32>);
+
+#[derive(Resource)]
+struct AutosaveTimer(Timer);
+
+pub struct SavePlugin;
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Player>()
+            .register_type::<CameraMode>()
+            .register_saved::<SaveKey>()
+            .register_saved::<Transform>()
+            .register_saved::<Velocity>()
+            .register_saved::<Stance>()
+            .register_saved::<CameraController>()
+            .init_resource::<PendingLoad>()
+            .insert_resource(AutosaveTimer(Timer::from_seconds(
+                AUTOSAVE_INTERVAL,
+                TimerMode::Repeating,
+            )))
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(load_pending)
+                    .with_system(quick_save_and_load)
+                    .with_system(autosave),
+            );
+    }
+}
+
+fn load_pending(
+    mut commands: Commands,
+    mut pending_load: ResMut<PendingLoad>,
+    player_query: Query<(), With<Player>>,
+) {
+    // Wait for the level to be spawned.
+    if player_query.is_empty() {
+        return;
+    }
+    if let Some(slot) = pending_load.0.take() {
+        commands.add(LoadGame { slot });
+    }
+}
+
+fn quick_save_and_load(mut commands: Commands, actions: Res<ActionState>) {
+    if actions.just_pressed(Action::QuickSave) {
+        commands.add(SaveGame {
+            slot: QUICKSAVE_SLOT,
+        });
+    }
+    if actions.just_pressed(Action::QuickLoad) {
+        commands.add(LoadGame {
+            slot: QUICKSAVE_SLOT,
+        });
+    }
+}
+
+fn autosave(mut commands: Commands, time: Res<Time>, mut timer: ResMut<AutosaveTimer>) {
+    if timer.0.tick(time.delta()).just_finished() {
+        commands.add(SaveGame {
+            slot: AUTOSAVE_SLOT,
+        });
+    }
+}
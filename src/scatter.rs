@@ -0,0 +1,319 @@
+use std::f32::consts::{SQRT_2, TAU};
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::auto_collider::{AutoCollider, MeshColliderKind};
+use crate::interaction::{Interactable, InteractionEvent};
+use crate::save::{DespawnSaved, SaveKey};
+use crate::AppState;
+
+/// Small deterministic random number generator (SplitMix64), so the same
+/// seed gives the same placements on every platform and build.
+pub struct Rng(u64);
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        // The upper 24 bits are exactly representable as f32.
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniform in `[min, max)`.
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    /// Uniform in `0..len`, `len` must not be zero.
+    pub fn index(&mut self, len: usize) -> usize {
+        (self.next_u64() % len as u64) as usize
+    }
+}
+
+/// Circle on the ground plane which is kept free of scattered props.
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct ExclusionZone {
+    /// Center on the ground plane, `x` and `z` of the world position.
+    pub center: Vec2,
+    pub radius: f32,
+}
+impl ExclusionZone {
+    fn contains(&self, point: Vec2) -> bool {
+        point.distance_squared(self.center) < self.radius * self.radius
+    }
+}
+
+fn default_scale_range() -> (f32, f32) {
+    (1.0, 1.0)
+}
+
+/// How trees are scattered, by [`ScatterRegion`]s of a level and in every
+/// chunk of a [`WorldStreaming`](crate::streaming::WorldStreaming) world.
+#[derive(Deserialize, Clone, Debug)]
+pub struct TreeScatter {
+    pub seed: u64,
+    /// No two trees are closer than this.
+    pub min_distance: f32,
+    /// Range the random scale of each tree is picked from.
+    #[serde(default = "default_scale_range")]
+    pub scale: (f32, f32),
+    /// Radius around every spawn point of the level which stays free.
+    #[serde(default)]
+    pub spawn_clearance: f32,
+    #[serde(default)]
+    pub auto_collider: Option<MeshColliderKind>,
+    /// Makes the trees choppable.
+    #[serde(default)]
+    pub interaction: Option<Interactable>,
+}
+
+/// Rectangle on the ground plane which is filled with trees by Poisson-disk
+/// sampling.
+#[derive(Deserialize, Clone, Debug)]
+pub struct ScatterRegion {
+    /// Unique name which matches the trees with their state in saves, so it
+    /// has to stay the same when the level is edited.
+    pub id: String,
+    /// Corners of the region on the ground plane, `x` and `z` of the world
+    /// position.
+    pub min: Vec2,
+    pub max: Vec2,
+    /// Height of the placed trees, unless the level has a procedural terrain
+    /// they are placed on.
+    #[serde(default)]
+    pub height: f32,
+    #[serde(default)]
+    pub exclusions: Vec<ExclusionZone>,
+    pub trees: TreeScatter,
+}
+impl ScatterRegion {
+    /// Transforms of all trees in the region, always the same for the same
+    /// region and spawn points.
+    pub fn placements(&self, spawn_points: &[Vec3]) -> Vec<Transform> {
+        let mut exclusions = self.exclusions.clone();
+        exclusions.extend(spawn_points.iter().map(|spawn_point| ExclusionZone {
+            center: Vec2::new(spawn_point.x, spawn_point.z),
+            radius: self.trees.spawn_clearance,
+        }));
+
+        let trees = &self.trees;
+        let mut rng = Rng::new(trees.seed);
+        poisson_disk(&mut rng, self.min, self.max, trees.min_distance)
+            .into_iter()
+            .filter(|point| !exclusions.iter().any(|zone| zone.contains(*point)))
+            .map(|point| {
+                let yaw = rng.range(0.0, TAU);
+                let scale = rng.range(trees.scale.0, trees.scale.1);
+                Transform::from_xyz(point.x, self.height, point.y)
+                    .with_rotation(Quat::from_rotation_y(yaw))
+                    .with_scale(Vec3::splat(scale))
+            })
+            .collect()
+    }
+}
+
+/// A scattered tree, which is removed when the player interacts with it.
+#[derive(Component)]
+pub struct Tree;
+
+/// Spawns a tree of a [`TreeScatter`] with the given key, for both level
+/// regions and streamed chunks.
+pub fn spawn_tree(
+    commands: &mut Commands,
+    scene: Handle<Scene>,
+    trees: &TreeScatter,
+    transform: Transform,
+    key: SaveKey,
+) -> Entity {
+    let mut entity = commands.spawn((
+        SceneBundle {
+            scene,
+            transform,
+            ..Default::default()
+        },
+        key,
+        Tree,
+    ));
+    if let Some(kind) = trees.auto_collider {
+        entity.insert(AutoCollider(kind));
+    }
+    if let Some(interactable) = &trees.interaction {
+        entity.insert(interactable.clone());
+    }
+    entity.id()
+}
+
+pub struct ScatterPlugin;
+impl Plugin for ScatterPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_update(AppState::InGame).with_system(chop_trees));
+    }
+}
+
+fn chop_trees(
+    mut commands: Commands,
+    mut events: EventReader<InteractionEvent>,
+    tree_query: Query<(), With<Tree>>,
+) {
+    for event in events.iter() {
+        if tree_query.contains(event.target) {
+            commands.add(DespawnSaved(event.target));
+        }
+    }
+}
+
+/// Bridson's algorithm: points in the rectangle from `min` to `max` with at
+/// least `radius` between them, filling the rectangle evenly.
+fn poisson_disk(rng: &mut Rng, min: Vec2, max: Vec2, radius: f32) -> Vec<Vec2> {
+    /// Candidates tried around a point before it stops being active.
+    const ATTEMPTS: usize = 30;
+
+    let size = max - min;
+    if radius <= 0.0 || size.x <= 0.0 || size.y <= 0.0 {
+        return Vec::new();
+    }
+    // Cells are small enough to hold at most one point.
+    let cell_size = radius / SQRT_2;
+    let columns = (size.x / cell_size).ceil() as usize;
+    let rows = (size.y / cell_size).ceil() as usize;
+    let mut grid: Vec<Option<usize>> = vec![None; columns * rows];
+    let cell = |point: Vec2| {
+        let relative = (point - min) / cell_size;
+        (
+            (relative.x as usize).min(columns - 1),
+            (relative.y as usize).min(rows - 1),
+        )
+    };
+
+    let mut points = Vec::new();
+    let mut active = Vec::new();
+    let first = min + size * Vec2::new(rng.next_f32(), rng.next_f32());
+    let (column, row) = cell(first);
+    grid[row * columns + column] = Some(0);
+    points.push(first);
+    active.push(0);
+
+    while !active.is_empty() {
+        let active_index = rng.index(active.len());
+        let center = points[active[active_index]];
+        let mut found = false;
+        for _ in 0..ATTEMPTS {
+            let angle = rng.range(0.0, TAU);
+            let distance = rng.range(radius, 2.0 * radius);
+            let candidate = center + Vec2::new(angle.cos(), angle.sin()) * distance;
+            if candidate.cmplt(min).any() || candidate.cmpge(max).any() {
+                continue;
+            }
+            let (column, row) = cell(candidate);
+            let too_close = (row.saturating_sub(2)..(row + 3).min(rows)).any(|neighbor_row| {
+                (column.saturating_sub(2)..(column + 3).min(columns)).any(|neighbor_column| {
+                    grid[neighbor_row * columns + neighbor_column]
+                        .map(|neighbor| {
+                            points[neighbor].distance_squared(candidate) < radius * radius
+                        })
+                        .unwrap_or(false)
+                })
+            });
+            if !too_close {
+                grid[row * columns + column] = Some(points.len());
+                active.push(points.len());
+                points.push(candidate);
+                found = true;
+                break;
+            }
+        }
+        if !found {
+            active.swap_remove(active_index);
+        }
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(seed: u64) -> ScatterRegion {
+        ScatterRegion {
+            id: "test".to_string(),
+            min: Vec2::new(-40.0, -30.0),
+            max: Vec2::new(40.0, 30.0),
+            height: 0.0,
+            exclusions: Vec::new(),
+            trees: TreeScatter {
+                seed,
+                min_distance: 4.0,
+                scale: (0.8, 1.2),
+                spawn_clearance: 0.0,
+                auto_collider: None,
+                interaction: None,
+            },
+        }
+    }
+
+    fn ground(transform: &Transform) -> Vec2 {
+        Vec2::new(transform.translation.x, transform.translation.z)
+    }
+
+    #[test]
+    fn same_seed_gives_same_placements() {
+        let first = region(7).placements(&[]);
+        let second = region(7).placements(&[]);
+        assert!(!first.is_empty());
+        assert_eq!(first, second);
+        assert_ne!(first, region(8).placements(&[]));
+    }
+
+    #[test]
+    fn placements_keep_min_distance() {
+        let region = region(3);
+        let points: Vec<Vec2> = region.placements(&[]).iter().map(ground).collect();
+        // An 80 by 60 rectangle fits far more than a handful of trees.
+        assert!(points.len() > 50, "only {} points", points.len());
+        for (index, a) in points.iter().enumerate() {
+            assert!(a.cmpge(region.min).all() && a.cmplt(region.max).all());
+            for b in &points[index + 1..] {
+                assert!(
+                    a.distance(*b) >= region.trees.min_distance,
+                    "{} and {} are too close",
+                    a,
+                    b
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn exclusions_and_spawn_points_stay_free() {
+        let mut region = region(5);
+        let zone = ExclusionZone {
+            center: Vec2::new(20.0, 10.0),
+            radius: 8.0,
+        };
+        region.exclusions.push(zone);
+        region.trees.spawn_clearance = 6.0;
+        let spawn_point = Vec3::new(-15.0, 2.0, -5.0);
+
+        let points: Vec<Vec2> = region
+            .placements(&[spawn_point])
+            .iter()
+            .map(ground)
+            .collect();
+        assert!(!points.is_empty());
+        for point in points {
+            assert!(point.distance(zone.center) >= zone.radius);
+            assert!(point.distance(Vec2::new(spawn_point.x, spawn_point.z)) >= 6.0);
+        }
+    }
+}
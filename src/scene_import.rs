@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy::scene::SceneInstance;
+use serde::Deserialize;
+
+/// What happens to camera nodes of an imported scene.
+#[derive(Deserialize, Clone, Copy, Default, Debug)]
+pub enum CameraImport {
+    #[default]
+    Keep,
+    Remove,
+}
+
+/// What happens to light nodes of an imported scene, like the ones glTF
+/// files get from `KHR_lights_punctual`.
+#[derive(Deserialize, Clone, Copy, Default, Debug)]
+pub enum LightImport {
+    #[default]
+    Keep,
+    Remove,
+    /// Multiplies the intensity, as exporters often use different units.
+    Scale(f32),
+}
+
+#[derive(Deserialize, Clone, Copy, Default, Debug)]
+#[serde(default)]
+pub struct SceneImport {
+    pub cameras: CameraImport,
+    pub lights: LightImport,
+}
+
+/// How cameras and lights of imported scenes are handled, so every placed
+/// prop doesn't bring its own.
+///
+/// Only the camera and light components are removed, the nodes stay in place
+/// for their children.
+#[derive(Resource, Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct SceneImportRules {
+    pub default: SceneImport,
+    /// Overrides by asset path, like `tree.glb#Scene0`.
+    pub assets: HashMap<String, SceneImport>,
+}
+impl SceneImportRules {
+    fn get(&self, path: &str) -> SceneImport {
+        self.assets.get(path).copied().unwrap_or(self.default)
+    }
+}
+
+/// Marks scene instances the [`SceneImportRules`] were applied to.
+#[derive(Component)]
+pub struct SceneImportApplied;
+
+pub struct SceneImportPlugin;
+impl Plugin for SceneImportPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SceneImportRules>()
+            .add_system(apply_scene_imports);
+    }
+}
+
+fn apply_scene_imports(
+    mut commands: Commands,
+    scene_spawner: Res<SceneSpawner>,
+    asset_server: Res<AssetServer>,
+    rules: Res<SceneImportRules>,
+    scene_query: Query<(Entity, &Handle<Scene>, &SceneInstance), Without<SceneImportApplied>>,
+    camera_query: Query<(), With<Camera>>,
+    mut point_light_query: Query<&mut PointLight>,
+    mut spot_light_query: Query<&mut SpotLight>,
+    mut directional_light_query: Query<&mut DirectionalLight>,
+) {
+    for (entity, scene, scene_instance) in scene_query.iter() {
+        if !scene_spawner.instance_is_ready(**scene_instance) {
+            continue;
+        }
+        commands.entity(entity).insert(SceneImportApplied);
+        let import = match asset_server.get_handle_path(scene) {
+            Some(asset_path) => {
+                // Same form as in the asset manifest, on every platform.
+                let path = asset_path.path().to_string_lossy().replace('\\', "/");
+                match asset_path.label() {
+                    Some(label) => rules.get(&format!("{}#{}", path, label)),
+                    None => rules.get(&path),
+                }
+            }
+            None => rules.default,
+        };
+
+        for scene_entity in scene_spawner.iter_instance_entities(**scene_instance) {
+            if matches!(import.cameras, CameraImport::Remove) && camera_query.contains(scene_entity)
+            {
+                commands.entity(scene_entity).remove::<(Camera, Camera3d)>();
+            }
+            match import.lights {
+                LightImport::Keep => {}
+                LightImport::Remove => {
+                    commands
+                        .entity(scene_entity)
+                        .remove::<(PointLight, SpotLight, DirectionalLight)>();
+                }
+                LightImport::Scale(factor) => {
+                    if let Ok(mut light) = point_light_query.get_mut(scene_entity) {
+                        light.intensity *= factor;
+                    }
+                    if let Ok(mut light) = spot_light_query.get_mut(scene_entity) {
+                        light.intensity *= factor;
+                    }
+                    if let Ok(mut light) = directional_light_query.get_mut(scene_entity) {
+                        light.illuminance *= factor;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::asset::AssetPlugin;
+    use bevy::scene::ScenePlugin;
+
+    use super::*;
+
+    /// Spawns a scene with a point light and a camera, and runs updates until
+    /// `rules` were applied to it.
+    fn import(rules: SceneImportRules) -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_plugin(ScenePlugin)
+            .register_type::<PointLight>()
+            .register_type::<Camera>()
+            .register_type::<Camera3d>()
+            .add_plugin(SceneImportPlugin)
+            .insert_resource(rules);
+
+        let mut world = World::new();
+        world.spawn(PointLight {
+            intensity: 100.0,
+            ..default()
+        });
+        world.spawn((Camera::default(), Camera3d::default()));
+        let scene = app
+            .world
+            .resource_mut::<Assets<Scene>>()
+            .add(Scene::new(world));
+        let root = app.world.spawn(SceneBundle { scene, ..default() }).id();
+
+        for _ in 0..10 {
+            app.update();
+            if app.world.get::<SceneImportApplied>(root).is_some() {
+                return app;
+            }
+        }
+        panic!("scene import rules weren't applied");
+    }
+
+    fn rules(cameras: CameraImport, lights: LightImport) -> SceneImportRules {
+        SceneImportRules {
+            default: SceneImport { cameras, lights },
+            assets: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn remove_drops_lights_and_cameras() {
+        let mut app = import(rules(CameraImport::Remove, LightImport::Remove));
+        assert_eq!(app.world.query::<&PointLight>().iter(&app.world).count(), 0);
+        assert_eq!(app.world.query::<&Camera>().iter(&app.world).count(), 0);
+        assert_eq!(app.world.query::<&Camera3d>().iter(&app.world).count(), 0);
+    }
+
+    #[test]
+    fn scale_multiplies_light_intensity() {
+        let mut app = import(rules(CameraImport::Keep, LightImport::Scale(0.5)));
+        let intensities: Vec<f32> = app
+            .world
+            .query::<&PointLight>()
+            .iter(&app.world)
+            .map(|light| light.intensity)
+            .collect();
+        assert_eq!(intensities, vec![50.0]);
+        assert_eq!(app.world.query::<&Camera>().iter(&app.world).count(), 1);
+    }
+}
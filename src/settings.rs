@@ -0,0 +1,258 @@
+use std::ops::RangeInclusive;
+
+use bevy::audio::AudioSink;
+use bevy::prelude::*;
+use bevy::window::{PresentMode, WindowMode};
+use serde::{Deserialize, Serialize};
+
+use crate::camera::CameraSettings;
+use crate::storage;
+
+/// Volume applied to every playing sound.
+#[derive(Resource, Reflect, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct AudioSettings {
+    /// Between `0.0` for muted and `1.0` for full volume.
+    pub volume: f32,
+}
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings { volume: 1.0 }
+    }
+}
+
+#[derive(Resource, Reflect, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    /// Borderless fullscreen instead of a window.
+    pub fullscreen: bool,
+    pub vsync: bool,
+    /// 4x multisample anti-aliasing, the only sample count besides 1 which
+    /// is supported everywhere.
+    pub msaa: bool,
+    /// Scaled by the time of day, see
+    /// [`DayNightSettings`](crate::day_night::DayNightSettings).
+    pub ambient_brightness: f32,
+}
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        GraphicsSettings {
+            fullscreen: false,
+            vsync: true,
+            msaa: true,
+            ambient_brightness: 0.2,
+        }
+    }
+}
+
+/// Everything persisted in the settings file.
+///
+/// Missing fields fall back to their defaults, so files written by older
+/// versions keep working.
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(default)]
+struct StoredSettings {
+    camera: CameraSettings,
+    audio: AudioSettings,
+    graphics: GraphicsSettings,
+}
+impl StoredSettings {
+    /// Reads the stored settings, falling back to the defaults if there are
+    /// none or they can't be parsed.
+    fn load() -> Self {
+        match storage::read("settings.ron") {
+            Some(contents) => StoredSettings::parse(&contents),
+            None => StoredSettings::default(),
+        }
+    }
+
+    fn parse(contents: &str) -> Self {
+        match ron::from_str::<StoredSettings>(contents) {
+            Ok(mut settings) => {
+                settings.validate();
+                settings
+            }
+            Err(err) => {
+                warn!("Ignoring corrupt settings, using defaults: {}", err);
+                StoredSettings::default()
+            }
+        }
+    }
+
+    fn save(&self) {
+        let result = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| err.to_string())
+            .and_then(|contents| storage::write("settings.ron", &contents));
+        if let Err(err) = result {
+            warn!("Could not save settings: {}", err);
+        }
+    }
+
+    /// Resets values which are out of range, like after editing the file by
+    /// hand.
+    fn validate(&mut self) {
+        let defaults = StoredSettings::default();
+        let camera = &mut self.camera;
+        validate_range(
+            "mouse_sensitivity",
+            &mut camera.mouse_sensitivity,
+            0.001..=0.1,
+            defaults.camera.mouse_sensitivity,
+        );
+        validate_range(
+            "scroll_line_sensitivity",
+            &mut camera.scroll_line_sensitivity,
+            0.0..=10.0,
+            defaults.camera.scroll_line_sensitivity,
+        );
+        validate_range(
+            "scroll_pixel_sensitivity",
+            &mut camera.scroll_pixel_sensitivity,
+            0.0..=1.0,
+            defaults.camera.scroll_pixel_sensitivity,
+        );
+        validate_range(
+            "volume",
+            &mut self.audio.volume,
+            0.0..=1.0,
+            defaults.audio.volume,
+        );
+        validate_range(
+            "ambient_brightness",
+            &mut self.graphics.ambient_brightness,
+            0.0..=1.0,
+            defaults.graphics.ambient_brightness,
+        );
+    }
+}
+
+fn validate_range(name: &str, value: &mut f32, range: RangeInclusive<f32>, default: f32) {
+    // Also catches NaN, which is never contained in a range.
+    if !range.contains(value) {
+        warn!(
+            "Setting {} is {}, outside of {:?}, using {}",
+            name, value, range, default
+        );
+        *value = default;
+    }
+}
+
+/// Loads the stored settings into the [`CameraSettings`], [`AudioSettings`]
+/// and [`GraphicsSettings`] resources and saves them whenever they change.
+pub struct SettingsPlugin;
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let settings = StoredSettings::load();
+        app.register_type::<AudioSettings>()
+            .register_type::<GraphicsSettings>()
+            .insert_resource(settings.camera)
+            .insert_resource(settings.audio)
+            .insert_resource(settings.graphics)
+            .add_system(apply_graphics_settings)
+            .add_system(apply_audio_settings)
+            .add_system(save_settings);
+    }
+}
+
+fn apply_graphics_settings(
+    settings: Res<GraphicsSettings>,
+    mut windows: ResMut<Windows>,
+    mut msaa: ResMut<Msaa>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    if let Some(window) = windows.get_primary_mut() {
+        window.set_mode(if settings.fullscreen {
+            WindowMode::BorderlessFullscreen
+        } else {
+            WindowMode::Windowed
+        });
+        window.set_present_mode(if settings.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        });
+    }
+    msaa.samples = if settings.msaa { 4 } else { 1 };
+}
+
+/// Sets the volume of all sounds whenever the settings change.
+///
+/// Sounds started later have to pass the volume in their
+/// [`PlaybackSettings`] themselves.
+fn apply_audio_settings(settings: Res<AudioSettings>, audio_sinks: Res<Assets<AudioSink>>) {
+    if !settings.is_changed() {
+        return;
+    }
+    for (_, sink) in audio_sinks.iter() {
+        sink.set_volume(settings.volume);
+    }
+}
+
+fn save_settings(
+    camera: Res<CameraSettings>,
+    audio: Res<AudioSettings>,
+    graphics: Res<GraphicsSettings>,
+) {
+    // The resources count as changed when they are inserted at startup,
+    // which doesn't need to be written back.
+    let changed = (camera.is_changed() && !camera.is_added())
+        || (audio.is_changed() && !audio.is_added())
+        || (graphics.is_changed() && !graphics.is_added());
+    if changed {
+        StoredSettings {
+            camera: camera.clone(),
+            audio: audio.clone(),
+            graphics: graphics.clone(),
+        }
+        .save();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unparsable_settings_fall_back_to_defaults() {
+        let settings = StoredSettings::parse("(camera: (mouse_sensitivity: ");
+        let defaults = StoredSettings::default();
+        assert_eq!(
+            settings.camera.mouse_sensitivity,
+            defaults.camera.mouse_sensitivity
+        );
+        assert_eq!(settings.audio.volume, defaults.audio.volume);
+        assert_eq!(settings.graphics.vsync, defaults.graphics.vsync);
+    }
+
+    #[test]
+    fn missing_fields_keep_the_others() {
+        let settings = StoredSettings::parse("(audio: (volume: 0.5), graphics: (vsync: false))");
+        let defaults = StoredSettings::default();
+        assert_eq!(settings.audio.volume, 0.5);
+        assert!(!settings.graphics.vsync);
+        assert_eq!(settings.graphics.msaa, defaults.graphics.msaa);
+        assert_eq!(
+            settings.camera.mouse_sensitivity,
+            defaults.camera.mouse_sensitivity
+        );
+    }
+
+    #[test]
+    fn nan_is_replaced_by_the_default() {
+        let settings = StoredSettings::parse("(audio: (volume: NaN))");
+        assert_eq!(settings.audio.volume, AudioSettings::default().volume);
+    }
+
+    #[test]
+    fn out_of_range_sensitivity_is_replaced_by_the_default() {
+        let settings = StoredSettings::parse("(camera: (mouse_sensitivity: 5.0, invert_y: true))");
+        assert_eq!(
+            settings.camera.mouse_sensitivity,
+            CameraSettings::default().mouse_sensitivity
+        );
+        // Valid values next to it are kept.
+        assert!(settings.camera.invert_y);
+    }
+}
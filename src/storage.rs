@@ -0,0 +1,61 @@
+//! Small named text files which persist between sessions, like settings and
+//! save games. Reading returns `None` if the file doesn't exist yet.
+
+/// Files in the platform's config directory: `$XDG_CONFIG_HOME` or
+/// `~/.config` on Linux, `%APPDATA%` on Windows and
+/// `~/Library/Application Support` on macOS.
+#[cfg(not(target_arch = "wasm32"))]
+mod platform {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn config_dir() -> Option<PathBuf> {
+        let base = if cfg!(target_os = "windows") {
+            env::var_os("APPDATA").map(PathBuf::from)
+        } else if cfg!(target_os = "macos") {
+            env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+        } else {
+            env::var_os("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .filter(|dir| dir.is_absolute())
+                .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        };
+        base.map(|base| base.join(env!("CARGO_PKG_NAME")))
+    }
+
+    pub fn read(name: &str) -> Option<String> {
+        fs::read_to_string(config_dir()?.join(name)).ok()
+    }
+
+    pub fn write(name: &str, contents: &str) -> Result<(), String> {
+        let dir = config_dir().ok_or("no config directory found")?;
+        fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+        fs::write(dir.join(name), contents).map_err(|err| err.to_string())
+    }
+}
+
+/// Entries in the browser's `localStorage`, prefixed with the crate name.
+#[cfg(target_arch = "wasm32")]
+mod platform {
+    fn local_storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok()?
+    }
+
+    fn key(name: &str) -> String {
+        format!("{}/{}", env!("CARGO_PKG_NAME"), name)
+    }
+
+    pub fn read(name: &str) -> Option<String> {
+        local_storage()?.get_item(&key(name)).ok()?
+    }
+
+    pub fn write(name: &str, contents: &str) -> Result<(), String> {
+        local_storage()
+            .ok_or("localStorage is not available")?
+            .set_item(&key(name), contents)
+            .map_err(|err| format!("{:?}", err))
+    }
+}
+
+pub use platform::{read, write};
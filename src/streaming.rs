@@ -0,0 +1,369 @@
+use bevy::ecs::system::CommandQueue;
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use bevy::utils::HashMap;
+use bevy_rapier3d::prelude::*;
+use futures_lite::future;
+use serde::{de, Deserialize, Deserializer};
+
+use crate::game_assets::GameAssets;
+use crate::level::LevelEntity;
+use crate::movement::keyboard_input;
+use crate::save::{RemovedSaveKeys, SaveKey};
+use crate::scatter::{spawn_tree, Rng, ScatterRegion, TreeScatter};
+use crate::terrain::{HeightSource, Splatting, Terrain, TerrainMaterial};
+use crate::{AppState, Player};
+
+/// Procedural world split into square chunks, which are generated in the
+/// background and spawned around the [`Player`].
+#[derive(Resource, Deserialize, Clone, Debug)]
+pub struct WorldStreaming {
+    pub chunk_size: f32,
+    /// Chunks in each direction around the player's chunk which are loaded.
+    /// Chunks are unloaded one chunk further out, so walking along a border
+    /// doesn't load and unload the same chunks over and over.
+    pub view_radius: i32,
+    /// Terrain vertices along each side of a chunk.
+    pub resolution: usize,
+    /// Only procedural sources are supported, as images can't be split into
+    /// chunks.
+    #[serde(deserialize_with = "deserialize_procedural_source")]
+    pub source: HeightSource,
+    /// Height of the bottom of the terrain.
+    #[serde(default)]
+    pub height: f32,
+    pub height_scale: f32,
+    /// How often textures repeat across a chunk, whole numbers keep them
+    /// seamless across chunk borders.
+    #[serde(default = "default_uv_scale")]
+    pub uv_scale: f32,
+    #[serde(default)]
+    pub splatting: Splatting,
+    /// Trees scattered in every chunk.
+    ///
+    /// Each chunk is sampled on its own, so trees on both sides of a chunk
+    /// border can be closer than `min_distance`.
+    #[serde(default)]
+    pub trees: Option<TreeScatter>,
+    /// Filled by the level with its spawn points, to keep trees away from them.
+    #[serde(skip)]
+    pub spawn_points: Vec<Vec3>,
+}
+
+fn default_uv_scale() -> f32 {
+    1.0
+}
+
+fn deserialize_procedural_source<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HeightSource, D::Error> {
+    match HeightSource::deserialize(deserializer)? {
+        HeightSource::Image(path) => Err(de::Error::custom(format!(
+            "streamed terrain can't use the heightmap image {}, only procedural sources",
+            path
+        ))),
+        source => Ok(source),
+    }
+}
+
+impl WorldStreaming {
+    fn chunk_center(&self, coordinates: IVec2) -> Vec2 {
+        (coordinates.as_vec2() + 0.5) * self.chunk_size
+    }
+
+    fn chunk_coordinates(&self, position: Vec3) -> IVec2 {
+        IVec2::new(
+            (position.x / self.chunk_size).floor() as i32,
+            (position.z / self.chunk_size).floor() as i32,
+        )
+    }
+
+    fn chunk_terrain(&self, coordinates: IVec2) -> Terrain {
+        let center = self.chunk_center(coordinates);
+        Terrain {
+            source: self.source.clone(),
+            size: Vec2::splat(self.chunk_size),
+            resolution: self.resolution,
+            height_scale: self.height_scale,
+            translation: Vec3::new(center.x, self.height, center.y),
+            uv_scale: self.uv_scale,
+            splatting: self.splatting.clone(),
+        }
+    }
+
+    /// Builds everything in a chunk. Runs on the [`AsyncComputeTaskPool`].
+    fn generate_chunk(&self, coordinates: IVec2) -> ChunkData {
+        let terrain = self.chunk_terrain(coordinates);
+        let terrain_data = terrain
+            .heights(None)
+            .map(|heights| (terrain.mesh(&heights), terrain.collider(&heights)));
+
+        ChunkData {
+            terrain_translation: terrain.translation,
+            terrain: terrain_data,
+            trees: self.chunk_trees(coordinates),
+        }
+    }
+
+    /// Transforms of the trees in a chunk, always the same for the same
+    /// chunk.
+    fn chunk_trees(&self, coordinates: IVec2) -> Vec<Transform> {
+        let Some(trees) = &self.trees else {
+            return Vec::new();
+        };
+        let terrain = self.chunk_terrain(coordinates);
+        let center = self.chunk_center(coordinates);
+        let half_size = Vec2::splat(self.chunk_size / 2.0);
+        let chunk_bits = ((coordinates.x as u32 as u64) << 32) | coordinates.y as u32 as u64;
+        let region = ScatterRegion {
+            // Streamed trees are keyed by their chunk, see `tree_key`.
+            id: String::new(),
+            min: center - half_size,
+            max: center + half_size,
+            height: self.height,
+            exclusions: Vec::new(),
+            trees: TreeScatter {
+                seed: Rng::new(trees.seed ^ chunk_bits).next_u64(),
+                ..trees.clone()
+            },
+        };
+        region
+            .placements(&self.spawn_points)
+            .into_iter()
+            .map(|mut transform| {
+                if let Some(height) =
+                    terrain.procedural_height(transform.translation.x, transform.translation.z)
+                {
+                    transform.translation.y = height;
+                }
+                transform
+            })
+            .collect()
+    }
+}
+
+/// Key of the tree at `index` of the chunk, which keeps chopped trees from
+/// growing back when the chunk is loaded again.
+fn tree_key(coordinates: IVec2, index: usize) -> SaveKey {
+    SaveKey(format!(
+        "chunk:{}:{}:{}",
+        coordinates.x, coordinates.y, index
+    ))
+}
+
+struct ChunkData {
+    terrain_translation: Vec3,
+    terrain: Option<(Mesh, Collider)>,
+    trees: Vec<Transform>,
+}
+
+enum ChunkState {
+    Generating(Task<ChunkData>),
+    Loaded(Entity),
+}
+
+/// Root entity of a spawned chunk with its coordinates, its trees are
+/// children.
+#[derive(Component)]
+pub struct Chunk(pub IVec2);
+
+/// All chunks which are loaded or being generated.
+#[derive(Resource, Default)]
+struct Chunks {
+    states: HashMap<IVec2, ChunkState>,
+    /// Shared by the terrain of all chunks, created with the first one.
+    material: Option<Handle<TerrainMaterial>>,
+}
+impl Chunks {
+    fn clear(&mut self) {
+        self.states.clear();
+        self.material = None;
+    }
+}
+
+pub struct StreamingPlugin;
+impl Plugin for StreamingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Chunks>().add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .with_system(update_chunks)
+                .with_system(spawn_generated_chunks.after(update_chunks))
+                .with_system(hold_player_above_missing_ground.after(keyboard_input)),
+        );
+    }
+}
+
+/// Starts generating chunks which came into view and unloads chunks which
+/// are too far away.
+fn update_chunks(
+    mut commands: Commands,
+    streaming: Option<Res<WorldStreaming>>,
+    mut chunks: ResMut<Chunks>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    let Some(streaming) = streaming else {
+        chunks.clear();
+        return;
+    };
+    // A new level despawned all chunks of the previous one.
+    if streaming.is_changed() {
+        chunks.clear();
+    }
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+
+    let center = streaming.chunk_coordinates(player_transform.translation);
+    let radius = streaming.view_radius;
+    chunks.states.retain(|coordinates, state| {
+        let distance = (*coordinates - center).abs().max_element();
+        if distance <= radius + 1 {
+            return true;
+        }
+        // Dropping a task cancels it.
+        if let ChunkState::Loaded(entity) = state {
+            commands.entity(*entity).despawn_recursive();
+        }
+        false
+    });
+
+    let task_pool = AsyncComputeTaskPool::get();
+    for x in -radius..=radius {
+        for z in -radius..=radius {
+            let coordinates = center + IVec2::new(x, z);
+            if chunks.states.contains_key(&coordinates) {
+                continue;
+            }
+            let streaming = WorldStreaming::clone(&streaming);
+            let task = task_pool.spawn(async move { streaming.generate_chunk(coordinates) });
+            chunks
+                .states
+                .insert(coordinates, ChunkState::Generating(task));
+        }
+    }
+}
+
+fn spawn_generated_chunks(
+    mut commands: Commands,
+    mut chunks: ResMut<Chunks>,
+    game_assets: Res<GameAssets>,
+    streaming: Option<Res<WorldStreaming>>,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<TerrainMaterial>>,
+    removed_keys: Option<Res<RemovedSaveKeys>>,
+) {
+    let Some(streaming) = streaming else {
+        return;
+    };
+    let Chunks { states, material } = &mut *chunks;
+    for (coordinates, state) in states.iter_mut() {
+        let ChunkState::Generating(task) = state else {
+            continue;
+        };
+        let Some(chunk_data) = future::block_on(future::poll_once(task)) else {
+            continue;
+        };
+
+        let chunk = commands
+            .spawn((
+                SpatialBundle::from_transform(Transform::from_translation(
+                    chunk_data.terrain_translation,
+                )),
+                Chunk(*coordinates),
+                LevelEntity,
+            ))
+            .id();
+        if let Some((mesh, collider)) = chunk_data.terrain {
+            let material = material
+                .get_or_insert_with(|| materials.add(streaming.splatting.material(&asset_server)))
+                .clone();
+            commands
+                .entity(chunk)
+                .insert((meshes.add(mesh), material, RigidBody::Fixed, collider));
+        }
+        if let Some(trees) = &streaming.trees {
+            for (index, transform) in chunk_data.trees.into_iter().enumerate() {
+                let key = tree_key(*coordinates, index);
+                if removed_keys
+                    .as_ref()
+                    .map_or(false, |removed| removed.0.contains(&key))
+                {
+                    continue;
+                }
+                let tree = spawn_tree(
+                    &mut commands,
+                    game_assets.tree.clone(),
+                    trees,
+                    relative_to_chunk(transform, chunk_data.terrain_translation),
+                    key,
+                );
+                commands.entity(chunk).add_child(tree);
+            }
+        }
+        *state = ChunkState::Loaded(chunk);
+    }
+}
+
+/// Trees are children of their chunk, placed relative to it.
+fn relative_to_chunk(transform: Transform, chunk_translation: Vec3) -> Transform {
+    Transform {
+        translation: transform.translation - chunk_translation,
+        ..transform
+    }
+}
+
+/// Spawns the streamed tree with the given key again if its chunk is
+/// loaded, see [`SavedEntitySpawner`](crate::save::SavedEntitySpawner).
+pub fn respawn_saved_tree(world: &mut World, key: &SaveKey) -> Option<Entity> {
+    let mut parts = key.0.strip_prefix("chunk:")?.split(':');
+    let coordinates = IVec2::new(parts.next()?.parse().ok()?, parts.next()?.parse().ok()?);
+    let index: usize = parts.next()?.parse().ok()?;
+    let (chunk, chunk_transform) = world
+        .query::<(Entity, &Chunk, &Transform)>()
+        .iter(world)
+        .find(|(_, chunk, _)| chunk.0 == coordinates)
+        .map(|(entity, _, transform)| (entity, transform.translation))?;
+
+    let mut queue = CommandQueue::default();
+    let tree = {
+        let streaming = world.get_resource::<WorldStreaming>()?;
+        let trees = streaming.trees.as_ref()?;
+        let transform = *streaming.chunk_trees(coordinates).get(index)?;
+        let scene = world.get_resource::<GameAssets>()?.tree.clone();
+        let mut commands = Commands::new(&mut queue, world);
+        let tree = spawn_tree(
+            &mut commands,
+            scene,
+            trees,
+            relative_to_chunk(transform, chunk_transform),
+            key.clone(),
+        );
+        commands.entity(chunk).add_child(tree);
+        tree
+    };
+    queue.apply(world);
+    Some(tree)
+}
+
+/// Keeps the player in place while the chunk below it has no collider yet,
+/// like right after spawning, so it doesn't fall through the ground.
+fn hold_player_above_missing_ground(
+    streaming: Option<Res<WorldStreaming>>,
+    chunk_query: Query<&Chunk, With<Collider>>,
+    mut player_query: Query<
+        (&Transform, &mut Velocity, &mut KinematicCharacterController),
+        With<Player>,
+    >,
+) {
+    let Some(streaming) = streaming else {
+        return;
+    };
+    for (transform, mut velocity, mut character_controller) in player_query.iter_mut() {
+        let coordinates = streaming.chunk_coordinates(transform.translation);
+        if !chunk_query.iter().any(|chunk| chunk.0 == coordinates) {
+            velocity.linvel = Vec3::ZERO;
+            character_controller.translation = None;
+        }
+    }
+}
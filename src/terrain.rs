@@ -0,0 +1,527 @@
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::render::mesh::Indices;
+use bevy::render::render_resource::{
+    AddressMode, AsBindGroup, FilterMode, PrimitiveTopology, SamplerDescriptor, ShaderRef,
+    TextureFormat,
+};
+use bevy::render::texture::ImageSampler;
+use bevy_rapier3d::prelude::*;
+use serde::Deserialize;
+
+use crate::scatter::Rng;
+
+#[derive(Deserialize, Clone, Debug)]
+pub enum HeightSource {
+    /// Asset path of a grayscale image, black is at the height of the
+    /// terrain's translation and white `height_scale` above it.
+    Image(String),
+    /// Fractal value noise with the given number of octaves, sampled at world
+    /// positions so neighboring terrains line up.
+    Noise {
+        seed: u64,
+        /// Features per meter of the first octave.
+        frequency: f32,
+        octaves: u32,
+    },
+}
+
+/// Blends grass, rock and snow textures depending on the slope and height of
+/// each vertex.
+///
+/// The weights of the three layers are stored in the red, green and blue
+/// vertex colors, which the [`TerrainMaterial`] blends the textures with.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Splatting {
+    /// Asset paths of the tiling textures.
+    pub grass: String,
+    pub rock: String,
+    pub snow: String,
+    /// Slopes in degrees over which grass blends into rock.
+    pub rock_slope: (f32, f32),
+    /// Heights above the bottom of the terrain over which the ground blends
+    /// into snow.
+    pub snow_height: (f32, f32),
+}
+impl Default for Splatting {
+    fn default() -> Self {
+        Splatting {
+            grass: "textures/grass.png".to_string(),
+            rock: "textures/rock.png".to_string(),
+            snow: "textures/snow.png".to_string(),
+            rock_slope: (30.0, 45.0),
+            snow_height: (f32::INFINITY, f32::INFINITY),
+        }
+    }
+}
+impl Splatting {
+    /// Weights of grass, rock and snow, which add up to one.
+    fn weights(&self, slope: f32, height: f32) -> [f32; 3] {
+        let rock = smoothstep(self.rock_slope, slope);
+        let snow = smoothstep(self.snow_height, height);
+        [(1.0 - rock) * (1.0 - snow), rock * (1.0 - snow), snow]
+    }
+
+    /// Material with the textures, terrains with the same splatting can share
+    /// one.
+    pub fn material(&self, asset_server: &AssetServer) -> TerrainMaterial {
+        TerrainMaterial {
+            grass: asset_server.load(self.grass.as_str()),
+            rock: asset_server.load(self.rock.as_str()),
+            snow: asset_server.load(self.snow.as_str()),
+        }
+    }
+}
+
+/// Blends the [`Splatting`] textures by the weights in the vertex colors,
+/// see `assets/shaders/terrain.wgsl`.
+#[derive(AsBindGroup, TypeUuid, Clone, Debug)]
+#[uuid = "4f0d2a8e-6c1b-4b7e-9a53-2d8e51f3c6a7"]
+pub struct TerrainMaterial {
+    #[texture(0)]
+    #[sampler(1)]
+    pub grass: Handle<Image>,
+    #[texture(2)]
+    #[sampler(3)]
+    pub rock: Handle<Image>,
+    #[texture(4)]
+    #[sampler(5)]
+    pub snow: Handle<Image>,
+}
+impl Material for TerrainMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/terrain.wgsl".into()
+    }
+}
+
+fn default_uv_scale() -> f32 {
+    1.0
+}
+
+/// Ground built from a heightmap, with a render mesh and a matching
+/// heightfield collider.
+///
+/// The mesh and collider are added to the entity as soon as the heights are
+/// available, which for images is once the image finished loading.
+#[derive(Component, Deserialize, Clone, Debug)]
+pub struct Terrain {
+    pub source: HeightSource,
+    /// Extent along `x` and `z`, centered on `translation`.
+    pub size: Vec2,
+    /// Vertices along each side, for both the mesh and the collider.
+    pub resolution: usize,
+    pub height_scale: f32,
+    pub translation: Vec3,
+    /// How often textures repeat across the whole terrain.
+    #[serde(default = "default_uv_scale")]
+    pub uv_scale: f32,
+    #[serde(default)]
+    pub splatting: Splatting,
+}
+impl Terrain {
+    /// World height of the ground below the given world position, for sources
+    /// which don't need any assets. Returns `None` for image heightmaps.
+    pub fn procedural_height(&self, x: f32, z: f32) -> Option<f32> {
+        match self.source {
+            HeightSource::Image(_) => None,
+            HeightSource::Noise {
+                seed,
+                frequency,
+                octaves,
+            } => {
+                let noise = fractal_noise(seed, x * frequency, z * frequency, octaves);
+                Some(self.translation.y + noise * self.height_scale)
+            }
+        }
+    }
+
+    /// Position relative to the terrain of the vertex in `row` along `z` and
+    /// `column` along `x`.
+    fn vertex_position(&self, row: usize, column: usize) -> Vec2 {
+        let steps = (self.resolution - 1) as f32;
+        Vec2::new(
+            (column as f32 / steps - 0.5) * self.size.x,
+            (row as f32 / steps - 0.5) * self.size.y,
+        )
+    }
+
+    /// Heights relative to the terrain, row by row along `z`.
+    pub fn heights(&self, heightmap: Option<&Image>) -> Option<Vec<f32>> {
+        let resolution = self.resolution;
+        let steps = (resolution - 1) as f32;
+        let mut heights = Vec::with_capacity(resolution * resolution);
+        for row in 0..resolution {
+            for column in 0..resolution {
+                let value = match self.source {
+                    HeightSource::Image(_) => {
+                        sample_image(heightmap?, column as f32 / steps, row as f32 / steps)?
+                    }
+                    HeightSource::Noise {
+                        seed,
+                        frequency,
+                        octaves,
+                    } => {
+                        let position = self.vertex_position(row, column)
+                            + Vec2::new(self.translation.x, self.translation.z);
+                        fractal_noise(
+                            seed,
+                            position.x * frequency,
+                            position.y * frequency,
+                            octaves,
+                        )
+                    }
+                };
+                heights.push(value * self.height_scale);
+            }
+        }
+        Some(heights)
+    }
+
+    pub fn mesh(&self, heights: &[f32]) -> Mesh {
+        let resolution = self.resolution;
+        let height = |row: usize, column: usize| heights[row * resolution + column];
+
+        let mut positions = Vec::with_capacity(heights.len());
+        let mut normals = Vec::with_capacity(heights.len());
+        let mut uvs = Vec::with_capacity(heights.len());
+        let mut colors = Vec::with_capacity(heights.len());
+        for row in 0..resolution {
+            for column in 0..resolution {
+                let position = self.vertex_position(row, column);
+                let y = height(row, column);
+                positions.push([position.x, y, position.y]);
+
+                // Central differences, one-sided at the border.
+                let (left, right) = (column.saturating_sub(1), (column + 1).min(resolution - 1));
+                let (back, front) = (row.saturating_sub(1), (row + 1).min(resolution - 1));
+                let dx = self.vertex_position(row, right).x - self.vertex_position(row, left).x;
+                let dz =
+                    self.vertex_position(front, column).y - self.vertex_position(back, column).y;
+                let normal = Vec3::new(
+                    -(height(row, right) - height(row, left)) / dx,
+                    1.0,
+                    -(height(front, column) - height(back, column)) / dz,
+                )
+                .normalize();
+                normals.push(normal.to_array());
+
+                let steps = (resolution - 1) as f32;
+                uvs.push([
+                    column as f32 / steps * self.uv_scale,
+                    row as f32 / steps * self.uv_scale,
+                ]);
+
+                let slope = normal.y.clamp(-1.0, 1.0).acos().to_degrees();
+                let [grass, rock, snow] = self.splatting.weights(slope, y);
+                colors.push([grass, rock, snow, 1.0]);
+            }
+        }
+
+        let mut indices = Vec::with_capacity((resolution - 1) * (resolution - 1) * 6);
+        for row in 0..resolution - 1 {
+            for column in 0..resolution - 1 {
+                let index = (row * resolution + column) as u32;
+                let next_row = index + resolution as u32;
+                indices.extend_from_slice(&[
+                    index,
+                    next_row,
+                    index + 1,
+                    index + 1,
+                    next_row,
+                    next_row + 1,
+                ]);
+            }
+        }
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+        mesh.set_indices(Some(Indices::U32(indices)));
+        mesh
+    }
+
+    pub fn collider(&self, heights: &[f32]) -> Collider {
+        // Rapier's heightfield has its rows along `z` like the mesh, but
+        // expects the heights in column-major order.
+        let resolution = self.resolution;
+        let column_major = (0..resolution)
+            .flat_map(|column| (0..resolution).map(move |row| heights[row * resolution + column]))
+            .collect();
+        Collider::heightfield(
+            column_major,
+            resolution,
+            resolution,
+            Vec3::new(self.size.x, 1.0, self.size.y),
+        )
+    }
+}
+
+/// The heightmap image of a terrain using [`HeightSource::Image`].
+#[derive(Component)]
+pub struct TerrainHeightmap(pub Handle<Image>);
+
+/// Spawns the entity of a terrain, which gets its mesh and collider from
+/// [`build_terrain`].
+pub fn spawn_terrain(
+    commands: &mut Commands,
+    terrain: &Terrain,
+    asset_server: &AssetServer,
+) -> Entity {
+    let mut entity = commands.spawn((
+        terrain.clone(),
+        SpatialBundle::from_transform(Transform::from_translation(terrain.translation)),
+    ));
+    if let HeightSource::Image(path) = &terrain.source {
+        entity.insert(TerrainHeightmap(asset_server.load(path.as_str())));
+    }
+    entity.id()
+}
+
+pub struct TerrainPlugin;
+impl Plugin for TerrainPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(MaterialPlugin::<TerrainMaterial>::default())
+            .add_system(build_terrain)
+            .add_system(repeat_splatting_textures);
+    }
+}
+
+fn build_terrain(
+    mut commands: Commands,
+    terrain_query: Query<(Entity, &Terrain, Option<&TerrainHeightmap>), Without<Collider>>,
+    images: Res<Assets<Image>>,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<TerrainMaterial>>,
+) {
+    for (entity, terrain, heightmap) in terrain_query.iter() {
+        if terrain.resolution < 2 {
+            error!("Terrain needs a resolution of at least 2");
+            commands.entity(entity).remove::<Terrain>();
+            continue;
+        }
+        let heightmap = match heightmap {
+            Some(heightmap) => match images.get(&heightmap.0) {
+                Some(image) => Some(image),
+                // Still loading.
+                None => continue,
+            },
+            None => None,
+        };
+        let Some(heights) = terrain.heights(heightmap) else {
+            error!("Unsupported terrain heightmap, use a grayscale image");
+            commands.entity(entity).remove::<Terrain>();
+            continue;
+        };
+
+        commands.entity(entity).insert((
+            meshes.add(terrain.mesh(&heights)),
+            materials.add(terrain.splatting.material(&asset_server)),
+            RigidBody::Fixed,
+            terrain.collider(&heights),
+        ));
+    }
+}
+
+/// Makes the splatting textures tile, images are clamped at their edges by
+/// default.
+fn repeat_splatting_textures(
+    mut events: EventReader<AssetEvent<Image>>,
+    materials: Res<Assets<TerrainMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    for event in events.iter() {
+        let AssetEvent::Created { handle } = event else {
+            continue;
+        };
+        let splatted = materials.iter().any(|(_, material)| {
+            [&material.grass, &material.rock, &material.snow].contains(&handle)
+        });
+        if !splatted {
+            continue;
+        }
+        if let Some(image) = images.get_mut(handle) {
+            image.sampler_descriptor = ImageSampler::Descriptor(SamplerDescriptor {
+                address_mode_u: AddressMode::Repeat,
+                address_mode_v: AddressMode::Repeat,
+                mag_filter: FilterMode::Linear,
+                min_filter: FilterMode::Linear,
+                ..Default::default()
+            });
+        }
+    }
+}
+
+/// Bilinearly filtered value between `0.0` and `1.0` of the first channel at
+/// texture coordinates `u` and `v`.
+fn sample_image(image: &Image, u: f32, v: f32) -> Option<f32> {
+    let width = image.texture_descriptor.size.width as usize;
+    let height = image.texture_descriptor.size.height as usize;
+    let texel = |x: usize, y: usize| -> Option<f32> {
+        let index = y * width + x;
+        match image.texture_descriptor.format {
+            TextureFormat::R8Unorm => Some(*image.data.get(index)? as f32 / 255.0),
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => {
+                Some(*image.data.get(index * 4)? as f32 / 255.0)
+            }
+            TextureFormat::R16Uint => {
+                let bytes = image.data.get(index * 2..index * 2 + 2)?;
+                Some(u16::from_le_bytes([bytes[0], bytes[1]]) as f32 / u16::MAX as f32)
+            }
+            _ => None,
+        }
+    };
+    if width == 0 || height == 0 {
+        return None;
+    }
+    let x = u * (width - 1) as f32;
+    let y = v * (height - 1) as f32;
+    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+    let top = lerp(texel(x0, y0)?, texel(x1, y0)?, tx);
+    let bottom = lerp(texel(x0, y1)?, texel(x1, y1)?, tx);
+    Some(lerp(top, bottom, ty))
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Smooth transition from `0.0` to `1.0` while `x` goes from `edges.0` to
+/// `edges.1`.
+fn smoothstep(edges: (f32, f32), x: f32) -> f32 {
+    if edges.1 <= edges.0 {
+        return if x < edges.0 { 0.0 } else { 1.0 };
+    }
+    let t = ((x - edges.0) / (edges.1 - edges.0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Random value between `0.0` and `1.0` at an integer lattice point.
+fn lattice_value(seed: u64, x: i32, z: i32) -> f32 {
+    let coordinates = ((x as u32 as u64) << 32) | z as u32 as u64;
+    Rng::new(seed ^ coordinates).next_f32()
+}
+
+fn value_noise(seed: u64, x: f32, z: f32) -> f32 {
+    let (x0, z0) = (x.floor(), z.floor());
+    let tx = smoothstep((0.0, 1.0), x - x0);
+    let tz = smoothstep((0.0, 1.0), z - z0);
+    let (ix, iz) = (x0 as i32, z0 as i32);
+    let back = lerp(
+        lattice_value(seed, ix, iz),
+        lattice_value(seed, ix + 1, iz),
+        tx,
+    );
+    let front = lerp(
+        lattice_value(seed, ix, iz + 1),
+        lattice_value(seed, ix + 1, iz + 1),
+        tx,
+    );
+    lerp(back, front, tz)
+}
+
+/// Sum of `octaves` layers of value noise, each with double the frequency
+/// and half the amplitude of the previous one, normalized to `0.0..=1.0`.
+fn fractal_noise(seed: u64, x: f32, z: f32, octaves: u32) -> f32 {
+    let mut sum = 0.0;
+    let mut total_amplitude = 0.0;
+    let mut amplitude = 1.0;
+    let mut frequency = 1.0;
+    for octave in 0..octaves.max(1) {
+        sum += value_noise(
+            seed.wrapping_add(octave as u64),
+            x * frequency,
+            z * frequency,
+        ) * amplitude;
+        total_amplitude += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    sum / total_amplitude
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::mesh::VertexAttributeValues;
+    use bevy_rapier3d::rapier::parry::query::{Ray, RayCast};
+
+    use super::*;
+
+    fn terrain() -> Terrain {
+        Terrain {
+            source: HeightSource::Noise {
+                seed: 0,
+                frequency: 1.0,
+                octaves: 1,
+            },
+            // Not square, so swapped axes don't line up by accident.
+            size: Vec2::new(8.0, 4.0),
+            resolution: 5,
+            height_scale: 1.0,
+            translation: Vec3::ZERO,
+            uv_scale: 1.0,
+            splatting: Splatting::default(),
+        }
+    }
+
+    #[test]
+    fn collider_heights_match_mesh() {
+        let terrain = terrain();
+        // Different in every vertex and steeper along `z` than along `x`.
+        let heights: Vec<f32> = (0..terrain.resolution * terrain.resolution)
+            .map(|index| {
+                let (row, column) = (index / terrain.resolution, index % terrain.resolution);
+                row as f32 * 1.5 + column as f32 * 0.25 + ((row * column) % 3) as f32 * 0.1
+            })
+            .collect();
+        let mesh = terrain.mesh(&heights);
+        let collider = terrain.collider(&heights);
+
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            panic!("terrain mesh without positions");
+        };
+        assert_eq!(positions.len(), heights.len());
+        for &[x, y, z] in positions {
+            // Rays exactly on the outer edge may miss the heightfield.
+            let inset =
+                Vec2::new(x, z).clamp(-terrain.size / 2.0 + 0.001, terrain.size / 2.0 - 0.001);
+            let ray = Ray::new([inset.x, 100.0, inset.y].into(), [0.0, -1.0, 0.0].into());
+            let toi = collider
+                .raw
+                .cast_local_ray(&ray, 200.0, true)
+                .unwrap_or_else(|| panic!("no collider below ({}, {})", x, z));
+            let collider_height = 100.0 - toi;
+            assert!(
+                (collider_height - y).abs() < 0.01,
+                "collider is at {} but the mesh at {} at ({}, {})",
+                collider_height,
+                y,
+                x,
+                z
+            );
+        }
+    }
+
+    #[test]
+    fn splatting_weights_add_up_to_one() {
+        let splatting = Splatting {
+            rock_slope: (20.0, 40.0),
+            snow_height: (5.0, 10.0),
+            ..Default::default()
+        };
+        assert_eq!(splatting.weights(0.0, 0.0), [1.0, 0.0, 0.0]);
+        assert_eq!(splatting.weights(60.0, 0.0), [0.0, 1.0, 0.0]);
+        assert_eq!(splatting.weights(0.0, 20.0), [0.0, 0.0, 1.0]);
+        for (slope, height) in [(30.0, 7.5), (25.0, 2.0), (35.0, 9.0)] {
+            let sum: f32 = splatting.weights(slope, height).iter().sum();
+            assert!((sum - 1.0).abs() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,84 @@
+#!/usr/bin/env python3
+"""Writes the tiling terrain textures assets/textures/{grass,rock,snow}.png.
+
+The textures are placeholders made from tileable value noise, run this again
+after changing the colors or the noise. Only needs the standard library.
+"""
+
+import os
+import random
+import struct
+import zlib
+
+SIZE = 128
+TEXTURES = os.path.join(os.path.dirname(__file__), "..", "assets", "textures")
+
+
+def smoothstep(t):
+    return t * t * (3.0 - 2.0 * t)
+
+
+def value_noise(seed, cells):
+    """Noise with `cells` lattice cells per side, wrapping at the edges."""
+    rng = random.Random(seed)
+    lattice = [[rng.random() for _ in range(cells)] for _ in range(cells)]
+    pixels = []
+    for y in range(SIZE):
+        row = []
+        for x in range(SIZE):
+            fx, fy = x * cells / SIZE, y * cells / SIZE
+            x0, y0 = int(fx), int(fy)
+            x1, y1 = (x0 + 1) % cells, (y0 + 1) % cells
+            tx, ty = smoothstep(fx - x0), smoothstep(fy - y0)
+            top = lattice[y0][x0] + (lattice[y0][x1] - lattice[y0][x0]) * tx
+            bottom = lattice[y1][x0] + (lattice[y1][x1] - lattice[y1][x0]) * tx
+            row.append(top + (bottom - top) * ty)
+        pixels.append(row)
+    return pixels
+
+
+def fractal_noise(seed, octaves):
+    """Sum of octaves with doubling frequency and halving amplitude, 0 to 1."""
+    layers = [value_noise(seed + octave, 4 << octave) for octave in range(octaves)]
+    total = sum(0.5 ** octave for octave in range(octaves))
+    return [
+        [
+            sum(layer[y][x] * 0.5 ** octave for octave, layer in enumerate(layers)) / total
+            for x in range(SIZE)
+        ]
+        for y in range(SIZE)
+    ]
+
+
+def write_png(name, color, seed, octaves, contrast):
+    """`color` is the sRGB color at medium noise, `contrast` how far the
+    brightness varies around it."""
+    noise = fractal_noise(seed, octaves)
+    rows = b""
+    for y in range(SIZE):
+        rows += b"\x00"
+        for x in range(SIZE):
+            brightness = 1.0 + (noise[y][x] - 0.5) * 2.0 * contrast
+            rows += bytes(
+                max(0, min(255, round(channel * brightness * 255))) for channel in color
+            )
+
+    def chunk(kind, data):
+        body = kind + data
+        return struct.pack(">I", len(data)) + body + struct.pack(">I", zlib.crc32(body))
+
+    header = struct.pack(">IIBBBBB", SIZE, SIZE, 8, 2, 0, 0, 0)
+    png = (
+        b"\x89PNG\r\n\x1a\n"
+        + chunk(b"IHDR", header)
+        + chunk(b"IDAT", zlib.compress(rows, 9))
+        + chunk(b"IEND", b"")
+    )
+    os.makedirs(TEXTURES, exist_ok=True)
+    with open(os.path.join(TEXTURES, name), "wb") as file:
+        file.write(png)
+
+
+write_png("grass.png", (0.25, 0.45, 0.15), seed=1, octaves=4, contrast=0.35)
+write_png("rock.png", (0.45, 0.42, 0.4), seed=2, octaves=5, contrast=0.5)
+write_png("snow.png", (0.92, 0.93, 0.96), seed=3, octaves=3, contrast=0.08)
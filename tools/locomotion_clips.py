@@ -0,0 +1,120 @@
+#!/usr/bin/env python3
+"""Writes the placeholder player clips assets/human_locomotion.glb.
+
+human.glb isn't rigged, so the clips only bob and tilt its "Cube" node, which
+the game plays them on. The file holds the Idle, Walk, Run and Jump clips as
+Animation0 to Animation3, in that order, run this again after changing them.
+Only needs the standard library.
+"""
+
+import json
+import math
+import os
+import struct
+
+OUTPUT = os.path.join(os.path.dirname(__file__), "..", "assets", "human_locomotion.glb")
+# Must match the node name in human.glb.
+NODE = "Cube"
+
+# Clips as keyframes of (time, height, pitch, roll), angles in degrees.
+CLIPS = [
+    ("Idle", [(0.0, 0.0, 0.0, 0.0), (1.0, 0.02, 0.0, 1.0), (2.0, 0.0, 0.0, 0.0)]),
+    (
+        "Walk",
+        [
+            (0.0, 0.0, 0.0, 0.0),
+            (0.25, 0.05, 2.0, 3.0),
+            (0.5, 0.0, 0.0, 0.0),
+            (0.75, 0.05, 2.0, -3.0),
+            (1.0, 0.0, 0.0, 0.0),
+        ],
+    ),
+    (
+        "Run",
+        [
+            (0.0, 0.0, 8.0, 0.0),
+            (0.15, 0.1, 10.0, 5.0),
+            (0.3, 0.0, 8.0, 0.0),
+            (0.45, 0.1, 10.0, -5.0),
+            (0.6, 0.0, 8.0, 0.0),
+        ],
+    ),
+    ("Jump", [(0.0, 0.0, 0.0, 0.0), (0.2, 0.15, 12.0, 0.0), (0.5, 0.1, 6.0, 0.0)]),
+]
+
+FLOAT = 5126
+
+
+def rotation(pitch, roll):
+    """Quaternion of a pitch around X followed by a roll around Z."""
+    sx, cx = math.sin(math.radians(pitch) / 2), math.cos(math.radians(pitch) / 2)
+    sz, cz = math.sin(math.radians(roll) / 2), math.cos(math.radians(roll) / 2)
+    return [sx * cz, -sx * sz, cx * sz, cx * cz]
+
+
+def main():
+    gltf = {
+        "asset": {"version": "2.0", "generator": "tools/locomotion_clips.py"},
+        "scene": 0,
+        "scenes": [{"name": "Scene", "nodes": [0]}],
+        "nodes": [{"name": NODE}],
+        "accessors": [],
+        "bufferViews": [],
+        "animations": [],
+    }
+    data = bytearray()
+
+    def accessor(kind, values):
+        view = {"buffer": 0, "byteOffset": len(data), "byteLength": 4 * len(values)}
+        data.extend(struct.pack("<%df" % len(values), *values))
+        gltf["bufferViews"].append(view)
+        components = {"SCALAR": 1, "VEC3": 3, "VEC4": 4}[kind]
+        entry = {
+            "bufferView": len(gltf["bufferViews"]) - 1,
+            "componentType": FLOAT,
+            "count": len(values) // components,
+            "type": kind,
+        }
+        if kind == "SCALAR":
+            # Required for animation inputs.
+            entry["min"] = [min(values)]
+            entry["max"] = [max(values)]
+        gltf["accessors"].append(entry)
+        return len(gltf["accessors"]) - 1
+
+    for name, keyframes in CLIPS:
+        times = accessor("SCALAR", [key[0] for key in keyframes])
+        translations = accessor(
+            "VEC3", [value for key in keyframes for value in (0.0, key[1], 0.0)]
+        )
+        rotations = accessor(
+            "VEC4", [value for key in keyframes for value in rotation(key[2], key[3])]
+        )
+        gltf["animations"].append(
+            {
+                "name": name,
+                "samplers": [
+                    {"input": times, "output": translations, "interpolation": "LINEAR"},
+                    {"input": times, "output": rotations, "interpolation": "LINEAR"},
+                ],
+                "channels": [
+                    {"sampler": 0, "target": {"node": 0, "path": "translation"}},
+                    {"sampler": 1, "target": {"node": 0, "path": "rotation"}},
+                ],
+            }
+        )
+    gltf["buffers"] = [{"byteLength": len(data)}]
+
+    # Chunks are padded to 4 bytes, JSON with spaces and binary with zeros.
+    json_chunk = json.dumps(gltf, separators=(",", ":")).encode()
+    json_chunk += b" " * (-len(json_chunk) % 4)
+    data.extend(b"\0" * (-len(data) % 4))
+    length = 12 + 8 + len(json_chunk) + 8 + len(data)
+    with open(OUTPUT, "wb") as file:
+        file.write(struct.pack("<4sII", b"glTF", 2, length))
+        file.write(struct.pack("<I4s", len(json_chunk), b"JSON") + json_chunk)
+        file.write(struct.pack("<I4s", len(data), b"BIN\0") + bytes(data))
+
+
+if __name__ == "__main__":
+    main()